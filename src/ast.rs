@@ -7,7 +7,82 @@ pub enum Ast {
 
 #[derive(Debug, PartialEq)]
 pub enum Command {
+	Autosave(Option<bool>),
+	Clear,
 	Exit,
+	Explain(Expression),
+	Funcs,
+	Help(Option<String>),
+	Load(Option<String>),
+	Reset,
+	Save(Option<String>),
+	Set(Setting),
+	Vars,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+	Infix,
+	Rpn,
+}
+
+impl Mode {
+	pub fn from_name(name: &str) -> Option<Mode> {
+		match name {
+			"infix" => Some(Mode::Infix),
+			"rpn" => Some(Mode::Rpn),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Angle {
+	Degrees,
+	Radians,
+}
+
+impl Angle {
+	pub fn from_name(name: &str) -> Option<Angle> {
+		match name {
+			"deg" | "degrees" => Some(Angle::Degrees),
+			"rad" | "radians" => Some(Angle::Radians),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Notation {
+	Auto,
+	// always as many digits after the decimal point as the precision
+	Fixed,
+	Scientific,
+}
+
+impl Notation {
+	pub fn from_name(name: &str) -> Option<Notation> {
+		match name {
+			"auto" => Some(Notation::Auto),
+			"fixed" => Some(Notation::Fixed),
+			"sci" | "scientific" => Some(Notation::Scientific),
+			_ => None,
+		}
+	}
+}
+
+pub const MAX_PRECISION: usize = 17;
+
+pub const RADIXES: [u32; 4] = [2, 8, 10, 16];
+
+#[derive(Debug, PartialEq)]
+pub enum Setting {
+	Angle(Angle),
+	Mode(Mode),
+	Notation(Notation),
+	// None for as many digits as are needed
+	Precision(Option<usize>),
+	Radix(u32),
 }
 
 #[derive(Debug, PartialEq)]
@@ -16,13 +91,30 @@ pub enum Expression {
 	Binary(Binary),
 	Call(Call),
 	Literal(Literal),
+	Result(ResultRef),
 	Unary(Unary),
 	Variable(Variable),
 }
 
+impl Expression {
+	// whether evaluating it would change a variable, e.g. "1 + (x = 2)" or "x++"
+	pub fn assigns(&self) -> bool {
+		match self {
+			Expression::Assignment(_) => true,
+			Expression::Binary(b) => b.left.assigns() || b.right.assigns(),
+			Expression::Call(c) => c.params.iter().any(Expression::assigns),
+			Expression::Result(ResultRef::Offset(offset)) => offset.assigns(),
+			Expression::Unary(u) => u.right.assigns(),
+			Expression::Literal(_) | Expression::Result(_) | Expression::Variable(_) => false,
+		}
+	}
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Statement {
+	Const(Assignment),
 	DeleteVar(Variable),
+	SetResult(usize, Expression),
 }
 
 #[derive(Debug, PartialEq)]
@@ -53,12 +145,51 @@ pub enum BinaryOp {
 	RightShift,
 }
 
+impl BinaryOp {
+	pub fn symbol(&self) -> &'static str {
+		match self {
+			BinaryOp::BitAnd => "&",
+			BinaryOp::BitOr => "|",
+			BinaryOp::BitXor => "^",
+			BinaryOp::Divide => "/",
+			BinaryOp::Exponent => "**",
+			BinaryOp::LeftShift => "<<",
+			BinaryOp::Minus => "-",
+			BinaryOp::Modulo => "%",
+			BinaryOp::Multiply => "*",
+			BinaryOp::Plus => "+",
+			BinaryOp::RightShift => ">>",
+		}
+	}
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Call {
 	pub name: String,
 	pub params: Vec<Expression>,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum ResultRef {
+	// $$
+	Latest,
+	// $n
+	Number(usize),
+	// ans[-k], counting back from the latest result
+	Offset(Box<Expression>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RpnItem {
+	Binary(BinaryOp),
+	// pops one value for each parameter
+	Call(String),
+	Operand(Expression),
+	// e.g. swap
+	Stack(String),
+	Unary(UnaryOp),
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Unary {
 	pub op: UnaryOp,
@@ -67,10 +198,21 @@ pub struct Unary {
 
 #[derive(Debug, PartialEq)]
 pub enum UnaryOp {
+	Factorial,
 	Negate,
 	Not,
 }
 
+impl UnaryOp {
+	pub fn symbol(&self) -> &'static str {
+		match self {
+			UnaryOp::Factorial => "!",
+			UnaryOp::Negate => "-",
+			UnaryOp::Not => "!",
+		}
+	}
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Variable {
 	pub name: String,
@@ -78,5 +220,10 @@ pub struct Variable {
 
 #[derive(Debug, PartialEq)]
 pub enum Literal {
+	Date(i64),
+	Duration(i64),
+	// integers too large for an f64 to hold exactly
+	Integer(i128),
 	Number(f64),
+	String(String),
 }
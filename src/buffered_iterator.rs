@@ -25,4 +25,9 @@ impl<T, TIter: Iterator<Item = T>> BufferedIterator<T, TIter> {
 	pub fn push(&mut self, item: T) {
 		self.buf.push(item);
 	}
+
+	// bypasses any buffered items
+	pub fn inner_mut(&mut self) -> &mut TIter {
+		&mut self.itr
+	}
 }
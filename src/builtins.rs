@@ -0,0 +1,538 @@
+use crate::ast::Call;
+use crate::datetime;
+use crate::math;
+use crate::value::*;
+use std::env;
+
+// which of a builtin's numbers are angles; run always works in radians
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Angles {
+	None,
+	Params,
+	Result,
+}
+
+pub struct Builtin {
+	pub name: &'static str,
+	pub params: &'static [&'static str],
+	pub variadic: bool,
+	pub angles: Angles,
+	pub description: &'static str,
+	// shown by help, along with their results
+	pub examples: &'static [&'static str],
+	pub run: fn(&Call, &[Value]) -> Result<Value, String>,
+}
+
+impl Builtin {
+	pub fn signature(&self) -> String {
+		let mut params = self.params.join(", ");
+		if self.variadic {
+			params.push_str(", ...");
+		}
+
+		format!("{}({})", self.name, params)
+	}
+} // Builtin
+
+pub fn find(name: &str) -> Option<&'static Builtin> {
+	BUILTINS.iter().find(|b| b.name == name)
+}
+
+fn expect_number(call: &Call, val: &Value) -> Result<f64, String> {
+	match val.number() {
+		Some(n) => Ok(n),
+		None => Err(format!(
+			"Call to {}() expects number parameters, but found a {}",
+			call.name,
+			val.type_name()
+		)),
+	}
+}
+
+fn expect_integer(call: &Call, val: &Value) -> Result<i64, String> {
+	let n = expect_number(call, val)?;
+
+	match val.exact_integer() {
+		_ if n.fract() != 0f64 || !n.is_finite() => Err(format!(
+			"Call to {}() expects integer parameters, but found {}",
+			call.name, n
+		)),
+		None => Err(format!(
+			"Call to {}() has a parameter too large to be represented exactly: {}",
+			call.name, n
+		)),
+		Some(n) if n.unsigned_abs() > i64::MAX as u128 => Err(format!(
+			"Call to {}() expects integers of up to 64 bits, but found {}",
+			call.name, n
+		)),
+		Some(n) => Ok(n as i64),
+	}
+}
+
+fn expect_natural(call: &Call, val: &Value) -> Result<u64, String> {
+	let n = expect_integer(call, val)?;
+
+	if n < 0 {
+		Err(format!(
+			"Call to {}() expects non-negative integer parameters, but found {}",
+			call.name, n
+		))
+	} else {
+		Ok(n as u64)
+	}
+}
+
+fn expect_positive(call: &Call, val: &Value) -> Result<i64, String> {
+	let n = expect_integer(call, val)?;
+
+	if n < 1 {
+		Err(format!(
+			"Call to {}() expects a positive integer, but found {}",
+			call.name, n
+		))
+	} else {
+		Ok(n)
+	}
+}
+
+fn expect_date(call: &Call, val: &Value) -> Result<i64, String> {
+	match val {
+		Value::Date(millis) => Ok(*millis),
+		_ => Err(format!(
+			"Call to {}() expects a date parameter, but found a {}",
+			call.name,
+			val.type_name()
+		)),
+	}
+}
+
+fn expect_string(call: &Call, val: &Value) -> Result<String, String> {
+	match val {
+		Value::String(str) => Ok(str.clone()),
+		_ => Err(format!(
+			"Call to {}() expects a string parameter, but found a {}",
+			call.name,
+			val.type_name()
+		)),
+	}
+}
+
+fn expect_modulus(call: &Call, val: &Value) -> Result<i64, String> {
+	match expect_integer(call, val)? {
+		0 => Err(format!("Call to {}() has a modulus of zero", call.name)),
+		m => Ok(m),
+	}
+}
+
+// the gamma function has poles at zero and all negative integers
+fn expect_gamma_domain(call: &Call, val: &Value) -> Result<f64, String> {
+	let n = expect_number(call, val)?;
+
+	if n <= 0f64 && n.fract() == 0f64 {
+		Err(format!("Call to {}() is undefined for {}", call.name, n))
+	} else {
+		Ok(n)
+	}
+}
+
+// inverse sine and cosine are only defined from -1 to 1
+fn expect_unit_interval(call: &Call, val: &Value) -> Result<f64, String> {
+	let n = expect_number(call, val)?;
+
+	if !(-1f64..=1f64).contains(&n) {
+		Err(format!("Call to {}() is undefined for {}", call.name, n))
+	} else {
+		Ok(n)
+	}
+}
+
+fn integer(n: i64) -> Value {
+	Value::integer(n as i128)
+}
+
+fn exact(call: &Call, n: Option<i64>) -> Result<Value, String> {
+	match n {
+		Some(n) => Ok(integer(n)),
+		None => Err(format!(
+			"Result of {}() is too large for a 64-bit integer",
+			call.name
+		)),
+	}
+}
+
+fn no_inverse(a: i64, m: i64) -> String {
+	format!("{} has no inverse modulo {}", a, m)
+}
+
+fn run_env(call: &Call, args: &[Value]) -> Result<Value, String> {
+	let name = expect_string(call, &args[0])?;
+	match env::var(&name) {
+		Ok(str) => match str.trim().parse::<f64>() {
+			Ok(n) if n.is_finite() => Ok(Value::Number(n)),
+			_ => Err(format!(
+				"Environment variable \"{}\" is not a number: \"{}\"",
+				name, str
+			)),
+		},
+		Err(_) => Err(format!("Environment variable \"{}\" is not set", name)),
+	}
+}
+
+fn run_choose(call: &Call, args: &[Value]) -> Result<Value, String> {
+	let n = expect_natural(call, &args[0])?;
+	let k = expect_natural(call, &args[1])?;
+	Ok(Value::Number(math::choose(n, k)))
+}
+
+pub static BUILTINS: &[Builtin] = &[
+	Builtin {
+		name: "abs",
+		params: &["n"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the absolute value of n",
+		examples: &["abs(-7)"],
+		run: |call, args| Ok(Value::Number(expect_number(call, &args[0])?.abs())),
+	},
+	Builtin {
+		name: "acos",
+		params: &["x"],
+		variadic: false,
+		angles: Angles::Result,
+		description: "Returns the inverse cosine of x",
+		examples: &["acos(1)"],
+		run: |call, args| Ok(Value::Number(expect_unit_interval(call, &args[0])?.acos())),
+	},
+	Builtin {
+		name: "asin",
+		params: &["x"],
+		variadic: false,
+		angles: Angles::Result,
+		description: "Returns the inverse sine of x",
+		examples: &["asin(1)"],
+		run: |call, args| Ok(Value::Number(expect_unit_interval(call, &args[0])?.asin())),
+	},
+	Builtin {
+		name: "atan",
+		params: &["x"],
+		variadic: false,
+		angles: Angles::Result,
+		description: "Returns the inverse tangent of x",
+		examples: &["atan(1)"],
+		run: |call, args| Ok(Value::Number(expect_number(call, &args[0])?.atan())),
+	},
+	Builtin {
+		name: "atan2",
+		params: &["y", "x"],
+		variadic: false,
+		angles: Angles::Result,
+		description: "Returns the angle from the positive x axis to the point (x, y)",
+		examples: &["atan2(1, -1)"],
+		run: |call, args| {
+			let y = expect_number(call, &args[0])?;
+			let x = expect_number(call, &args[1])?;
+			Ok(Value::Number(y.atan2(x)))
+		},
+	},
+	Builtin {
+		name: "beta",
+		params: &["a", "b"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the beta function of a and b",
+		examples: &["beta(2, 3)"],
+		run: |call, args| {
+			let a = expect_gamma_domain(call, &args[0])?;
+			let b = expect_gamma_domain(call, &args[1])?;
+			expect_gamma_domain(call, &Value::Number(a + b))?;
+			Ok(Value::Number(math::beta(a, b)))
+		},
+	},
+	Builtin {
+		name: "ceil",
+		params: &["n"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the smallest integer greater than or equal to n",
+		examples: &["ceil(3.2)", "ceil(-3.2)"],
+		run: |call, args| Ok(Value::Number(expect_number(call, &args[0])?.ceil())),
+	},
+	Builtin {
+		name: "choose",
+		params: &["n", "k"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the number of ways to choose k of n items; alias for nCr",
+		examples: &["choose(5, 2)"],
+		run: run_choose,
+	},
+	Builtin {
+		name: "cos",
+		params: &["x"],
+		variadic: false,
+		angles: Angles::Params,
+		description: "Returns the cosine of angle x",
+		examples: &["cos(0)", "cos(pi)"],
+		run: |call, args| Ok(Value::Number(expect_number(call, &args[0])?.cos())),
+	},
+	Builtin {
+		name: "egcd",
+		params: &["a", "b"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns [g, x, y] such that a*x + b*y = g = gcd(a, b)",
+		examples: &["egcd(240, 46)"],
+		run: |call, args| {
+			let a = expect_integer(call, &args[0])?;
+			let b = expect_integer(call, &args[1])?;
+			let (g, x, y) = math::egcd(a, b);
+			Ok(Value::List(vec![integer(g), integer(x), integer(y)]))
+		},
+	},
+	Builtin {
+		name: "env",
+		params: &["name"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the number in environment variable name",
+		examples: &["env(\"SHLVL\")"],
+		run: run_env,
+	},
+	Builtin {
+		name: "fact",
+		params: &["n"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns n!; equivalent to the ! suffix operator",
+		examples: &["fact(6)"],
+		run: |call, args| {
+			let n = expect_natural(call, &args[0])?;
+			Ok(Value::Number(math::factorial(n)))
+		},
+	},
+	Builtin {
+		name: "factor",
+		params: &["n"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the prime factors of n",
+		examples: &["factor(360)"],
+		run: |call, args| {
+			let n = expect_positive(call, &args[0])?;
+			Ok(Value::List(
+				math::factor(n).into_iter().map(integer).collect(),
+			))
+		},
+	},
+	Builtin {
+		name: "floor",
+		params: &["n"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the largest integer less than or equal to n",
+		examples: &["floor(3.7)", "floor(-3.7)"],
+		run: |call, args| Ok(Value::Number(expect_number(call, &args[0])?.floor())),
+	},
+	Builtin {
+		name: "fromunix",
+		params: &["n"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the date n seconds after the unix epoch",
+		examples: &["fromunix(1_792_281_600)"],
+		run: |call, args| {
+			let millis = expect_number(call, &args[0])? * datetime::MILLIS_PER_SECOND as f64;
+			if !millis.is_finite() {
+				return Err("Date is out of range".to_string());
+			}
+			Value::date(millis.round() as i64)
+		},
+	},
+	Builtin {
+		name: "gamma",
+		params: &["x"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the gamma function of x",
+		examples: &["gamma(5)", "gamma(0.5)"],
+		run: |call, args| {
+			let x = expect_gamma_domain(call, &args[0])?;
+			Ok(Value::Number(math::gamma(x)))
+		},
+	},
+	Builtin {
+		name: "gcd",
+		params: &["n"],
+		variadic: true,
+		angles: Angles::None,
+		description: "Returns the greatest common divisor of all arguments",
+		examples: &["gcd(12, 18, 27)"],
+		run: |call, args| {
+			let mut g = 0;
+			for arg in args {
+				g = math::gcd(g, expect_integer(call, arg)?);
+			}
+			Ok(integer(g))
+		},
+	},
+	Builtin {
+		name: "isprime",
+		params: &["n"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns 1 if n is prime, otherwise 0",
+		examples: &["isprime(97)", "isprime(91)"],
+		run: |call, args| {
+			let n = expect_integer(call, &args[0])?;
+			Ok(integer(math::is_prime(n) as i64))
+		},
+	},
+	Builtin {
+		name: "lcm",
+		params: &["n"],
+		variadic: true,
+		angles: Angles::None,
+		description: "Returns the least common multiple of all arguments",
+		examples: &["lcm(4, 6, 10)"],
+		run: |call, args| {
+			let mut l = Some(1);
+			for arg in args {
+				let n = expect_integer(call, arg)?;
+				l = l.and_then(|l| math::lcm(l, n));
+			}
+			exact(call, l)
+		},
+	},
+	Builtin {
+		name: "lgamma",
+		params: &["x"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the natural log of the absolute value of gamma(x)",
+		examples: &["lgamma(10)"],
+		run: |call, args| {
+			let x = expect_gamma_domain(call, &args[0])?;
+			Ok(Value::Number(math::lgamma(x)))
+		},
+	},
+	Builtin {
+		name: "modinv",
+		params: &["a", "m"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns x such that a*x = 1 (mod m)",
+		examples: &["modinv(3, 11)"],
+		run: |call, args| {
+			let a = expect_integer(call, &args[0])?;
+			let m = expect_modulus(call, &args[1])?;
+			match math::modinv(a, m) {
+				Some(inv) => Ok(integer(inv)),
+				None => Err(no_inverse(a, m)),
+			}
+		},
+	},
+	Builtin {
+		name: "modpow",
+		params: &["b", "e", "m"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns b**e (mod m)",
+		examples: &["modpow(4, 13, 497)"],
+		run: |call, args| {
+			let b = expect_integer(call, &args[0])?;
+			let e = expect_integer(call, &args[1])?;
+			let m = expect_modulus(call, &args[2])?;
+			match math::modpow(b, e, m) {
+				Some(p) => Ok(integer(p)),
+				None => Err(no_inverse(b, m)),
+			}
+		},
+	},
+	Builtin {
+		name: "nCr",
+		params: &["n", "k"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the number of ways to choose k of n items",
+		examples: &["nCr(52, 5)"],
+		run: run_choose,
+	},
+	Builtin {
+		name: "nPr",
+		params: &["n", "k"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the number of ordered arrangements of k of n items",
+		examples: &["nPr(5, 2)"],
+		run: |call, args| {
+			let n = expect_natural(call, &args[0])?;
+			let k = expect_natural(call, &args[1])?;
+			Ok(Value::Number(math::permutations(n, k)))
+		},
+	},
+	Builtin {
+		name: "nextprime",
+		params: &["n"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the smallest prime greater than n",
+		examples: &["nextprime(89)"],
+		run: |call, args| {
+			let n = expect_integer(call, &args[0])?;
+			exact(call, math::next_prime(n))
+		},
+	},
+	Builtin {
+		name: "round",
+		params: &["n"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the nearest integer to n, rounding half-way cases away from 0",
+		examples: &["round(2.5)", "round(-2.5)"],
+		run: |call, args| Ok(Value::Number(expect_number(call, &args[0])?.round())),
+	},
+	Builtin {
+		name: "sin",
+		params: &["x"],
+		variadic: false,
+		angles: Angles::Params,
+		description: "Returns the sine of angle x",
+		examples: &["sin(pi / 2)"],
+		run: |call, args| Ok(Value::Number(expect_number(call, &args[0])?.sin())),
+	},
+	Builtin {
+		name: "tan",
+		params: &["x"],
+		variadic: false,
+		angles: Angles::Params,
+		description: "Returns the tangent of angle x",
+		examples: &["tan(0.5)"],
+		run: |call, args| Ok(Value::Number(expect_number(call, &args[0])?.tan())),
+	},
+	Builtin {
+		name: "totient",
+		params: &["n"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns Euler's totient of n, the count of integers up to n coprime to it",
+		examples: &["totient(36)"],
+		run: |call, args| {
+			let n = expect_positive(call, &args[0])?;
+			Ok(integer(math::totient(n)))
+		},
+	},
+	Builtin {
+		name: "unix",
+		params: &["date"],
+		variadic: false,
+		angles: Angles::None,
+		description: "Returns the number of seconds from the unix epoch to date",
+		examples: &["unix(2026-10-18)"],
+		run: |call, args| {
+			let millis = expect_date(call, &args[0])?;
+			Ok(Value::Number(
+				millis as f64 / datetime::MILLIS_PER_SECOND as f64,
+			))
+		},
+	},
+];
@@ -0,0 +1,129 @@
+use crate::builtins;
+use crate::parsing;
+use crate::running::*;
+
+fn is_name_char(c: char) -> bool {
+	c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+// variable names are a snapshot, refreshed as the session changes
+pub struct NameCompleter {
+	vars: Vec<String>,
+}
+
+impl NameCompleter {
+	pub fn new() -> NameCompleter {
+		NameCompleter {
+			vars: vec![],
+		}
+	}
+
+	pub fn update_vars(&mut self, runner: &Runner) {
+		let mut vars: Vec<String> = runner
+			.scopes()
+			.iter()
+			.flat_map(|scope| scope.keys().cloned())
+			.collect();
+		vars.extend(["now".to_string(), "today".to_string()]);
+
+		vars.sort();
+		self.vars = vars;
+	}
+
+	pub fn is_var(&self, name: &str) -> bool {
+		self.vars.binary_search_by(|var| var.as_str().cmp(name)).is_ok()
+	}
+
+	fn candidates(&self, prefix: &str, line_start: bool) -> Vec<String> {
+		let mut candidates: Vec<String> = self
+			.vars
+			.iter()
+			.filter(|name| name.starts_with(prefix))
+			.cloned()
+			.collect();
+
+		// functions are always called, so save typing the paren too
+		candidates.extend(
+			builtins::BUILTINS
+				.iter()
+				.filter(|b| b.name.starts_with(prefix))
+				.map(|b| format!("{}(", b.name)),
+		);
+
+		if line_start {
+			candidates.extend(
+				parsing::KEYWORDS
+					.iter()
+					.filter(|k| k.starts_with(prefix))
+					.map(|k| k.to_string()),
+			);
+		}
+
+		candidates.sort();
+		candidates.dedup();
+		candidates
+	}
+
+	// returns the start of the name being typed, and its completions
+	pub fn complete(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+		let start = line[..pos]
+			.char_indices()
+			.rev()
+			.take_while(|(_, c)| is_name_char(*c))
+			.last()
+			.map_or(pos, |(i, _)| i);
+
+		let prefix = &line[start..pos];
+		if prefix.is_empty() || prefix.starts_with(|c: char| c.is_ascii_digit()) {
+			return (start, vec![]);
+		}
+
+		let line_start = line[..start].trim().is_empty();
+		(start, self.candidates(prefix, line_start))
+	}
+} // NameCompleter
+
+#[cfg(test)]
+mod tests {
+	use crate::ast::*;
+	use crate::completion::*;
+	use crate::parsing::*;
+
+	fn complete(completer: &NameCompleter, line: &str) -> (usize, Vec<String>) {
+		completer.complete(line, line.len())
+	}
+
+	#[test]
+	fn complete_names() {
+		let mut runner = Runner::new();
+		match parse("monthly_budget_remaining = 5") {
+			Some(Ast::Expression(expr)) => runner.run_expression(&expr).unwrap(),
+			ast => panic!("Unexpected Ast: {:?}", ast),
+		};
+
+		let mut completer = NameCompleter::new();
+		completer.update_vars(&runner);
+
+		assert_eq!(
+			complete(&completer, "2 * mon"),
+			(4, vec!["monthly_budget_remaining".to_string()])
+		);
+		assert!(completer.is_var("monthly_budget_remaining"));
+		assert!(!completer.is_var("monthly"));
+		assert_eq!(complete(&completer, "gc"), (0, vec!["gcd(".to_string()]));
+		assert_eq!(complete(&completer, "de"), (0, vec!["delete".to_string()]));
+		assert_eq!(complete(&completer, "1 + de"), (4, vec![]));
+		assert_eq!(
+			complete(&completer, "phys.m_"),
+			(
+				0,
+				vec![
+					"phys.m_e".to_string(),
+					"phys.m_n".to_string(),
+					"phys.m_p".to_string()
+				]
+			)
+		);
+		assert_eq!(complete(&completer, "12"), (0, vec![]));
+	}
+}
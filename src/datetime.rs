@@ -0,0 +1,227 @@
+// dates are UTC milliseconds since the unix epoch, and durations milliseconds
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MILLIS_PER_SECOND: i64 = 1_000;
+pub const MILLIS_PER_MINUTE: i64 = 60 * MILLIS_PER_SECOND;
+pub const MILLIS_PER_HOUR: i64 = 60 * MILLIS_PER_MINUTE;
+pub const MILLIS_PER_DAY: i64 = 24 * MILLIS_PER_HOUR;
+
+// dates are limited to the years 0000 to 9999, which print and scan as 4 digits
+pub const MIN_DATE_MILLIS: i64 = -719_528 * MILLIS_PER_DAY;
+pub const MAX_DATE_MILLIS: i64 = 2_932_897 * MILLIS_PER_DAY - 1;
+
+// 100 million days either way
+pub const MAX_DURATION_MILLIS: i64 = 100_000_000 * MILLIS_PER_DAY;
+
+const UNITS: [(&str, i64); 6] = [
+	("w", 7 * MILLIS_PER_DAY),
+	("d", MILLIS_PER_DAY),
+	("h", MILLIS_PER_HOUR),
+	("min", MILLIS_PER_MINUTE),
+	("s", MILLIS_PER_SECOND),
+	("ms", 1),
+];
+
+pub fn unit_millis(unit: &str) -> Option<i64> {
+	UNITS.iter().find(|(u, _)| *u == unit).map(|(_, ms)| *ms)
+}
+
+pub fn now() -> i64 {
+	match SystemTime::now().duration_since(UNIX_EPOCH) {
+		Ok(d) => d.as_millis() as i64,
+		Err(e) => -(e.duration().as_millis() as i64),
+	}
+}
+
+pub fn today() -> i64 {
+	now().div_euclid(MILLIS_PER_DAY) * MILLIS_PER_DAY
+}
+
+// see http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+	let y = if month <= 2 { year - 1 } else { year };
+	let era = y.div_euclid(400);
+	let yoe = y.rem_euclid(400);
+	let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+	let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+	era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+	let z = days + 719_468;
+	let era = z.div_euclid(146_097);
+	let doe = z.rem_euclid(146_097);
+	let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+	(year, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+	let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+
+	match month {
+		2 if leap => 29,
+		2 => 28,
+		4 | 6 | 9 | 11 => 30,
+		_ => 31,
+	}
+}
+
+// YYYY-MM-DD[THH:MM[:SS[.fff]][Z|+HH:MM|-HH:MM]], as produced by the scanner
+pub fn parse_date(str: &str) -> Result<i64, String> {
+	let invalid = || format!("Invalid date \"{}\"", str);
+	let field = |range: std::ops::Range<usize>| -> Result<i64, String> {
+		str.get(range)
+			.and_then(|s| s.parse::<i64>().ok())
+			.ok_or_else(invalid)
+	};
+
+	let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+	if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+		return Err(invalid());
+	}
+
+	let mut millis = days_from_civil(year, month, day) * MILLIS_PER_DAY;
+	if str.len() == 10 {
+		return Ok(millis);
+	}
+
+	let (hour, minute) = (field(11..13)?, field(14..16)?);
+	if hour > 23 || minute > 59 {
+		return Err(invalid());
+	}
+	millis += hour * MILLIS_PER_HOUR + minute * MILLIS_PER_MINUTE;
+
+	let mut rest = &str[16..];
+	if let Some(secs) = rest.strip_prefix(':') {
+		let end = secs
+			.find(|c: char| !c.is_ascii_digit() && c != '.')
+			.unwrap_or(secs.len());
+		let seconds = secs[..end].parse::<f64>().map_err(|_| invalid())?;
+		if seconds >= 60f64 {
+			return Err(invalid());
+		}
+		millis += (seconds * MILLIS_PER_SECOND as f64).round() as i64;
+		rest = &secs[end..];
+	}
+
+	match rest {
+		"" | "Z" => {}
+		_ => {
+			let sign = if rest.starts_with('-') { -1 } else { 1 };
+			let hours = rest.get(1..3).and_then(|s| s.parse::<i64>().ok());
+			let minutes = rest.get(4..6).and_then(|s| s.parse::<i64>().ok());
+			match (hours, minutes) {
+				(Some(h), Some(m)) if h <= 23 && m <= 59 => {
+					millis -= sign * (h * MILLIS_PER_HOUR + m * MILLIS_PER_MINUTE)
+				}
+				_ => return Err(invalid()),
+			}
+		}
+	}
+
+	if !(MIN_DATE_MILLIS..=MAX_DATE_MILLIS).contains(&millis) {
+		return Err(format!("Date \"{}\" is out of range", str));
+	}
+
+	Ok(millis)
+} // parse_date
+
+pub fn format_date(millis: i64) -> String {
+	let (year, month, day) = civil_from_days(millis.div_euclid(MILLIS_PER_DAY));
+	let mut str = format!("{:04}-{:02}-{:02}", year, month, day);
+
+	let time = millis.rem_euclid(MILLIS_PER_DAY);
+	if time != 0 {
+		str.push_str(&format!(
+			"T{:02}:{:02}:{:02}",
+			time / MILLIS_PER_HOUR,
+			time % MILLIS_PER_HOUR / MILLIS_PER_MINUTE,
+			time % MILLIS_PER_MINUTE / MILLIS_PER_SECOND
+		));
+
+		if time % MILLIS_PER_SECOND != 0 {
+			str.push_str(&format!(".{:03}", time % MILLIS_PER_SECOND));
+		}
+
+		str.push('Z');
+	}
+
+	str
+}
+
+pub fn format_duration(millis: i64) -> String {
+	if millis == 0 {
+		return "0s".to_string();
+	}
+
+	let mut remaining = millis.unsigned_abs();
+	let mut parts = vec![];
+	for (unit, unit_millis) in UNITS.iter().skip(1) {
+		let count = remaining / *unit_millis as u64;
+		if count > 0 {
+			parts.push(format!("{}{}", count, unit));
+			remaining %= *unit_millis as u64;
+		}
+	}
+
+	let sign = if millis < 0 { "-" } else { "" };
+	format!("{}{}", sign, parts.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::datetime::*;
+
+	#[test]
+	fn test_civil_days() {
+		assert_eq!(days_from_civil(1970, 1, 1), 0);
+		assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+		assert_eq!(days_from_civil(1969, 12, 31), -1);
+		assert_eq!(civil_from_days(0), (1970, 1, 1));
+		assert_eq!(civil_from_days(20_744), (2026, 10, 18));
+		assert_eq!(civil_from_days(-719_468), (0, 3, 1));
+	}
+
+	#[test]
+	fn test_parse_date() {
+		assert_eq!(parse_date("1970-01-01"), Ok(0));
+		assert_eq!(parse_date("2026-10-18"), Ok(20_744 * MILLIS_PER_DAY));
+		assert_eq!(
+			parse_date("2026-10-18T12:00Z"),
+			Ok(20_744 * MILLIS_PER_DAY + 12 * MILLIS_PER_HOUR)
+		);
+		assert_eq!(parse_date("1970-01-01T00:00:01.5"), Ok(1_500));
+		assert_eq!(parse_date("1970-01-01T02:00+02:00"), Ok(0));
+		assert_eq!(parse_date("1970-01-01T00:00-00:30"), Ok(30 * MILLIS_PER_MINUTE));
+		assert_eq!(parse_date("2024-02-29"), Ok(19_782 * MILLIS_PER_DAY));
+		assert!(parse_date("2023-02-29").is_err());
+		assert!(parse_date("2026-13-01").is_err());
+		assert!(parse_date("2026-10-18T24:00").is_err());
+		assert_eq!(parse_date("0000-01-01"), Ok(MIN_DATE_MILLIS));
+		assert_eq!(parse_date("9999-12-31T23:59:59.999"), Ok(MAX_DATE_MILLIS));
+		assert!(parse_date("0000-01-01T00:00+00:01").is_err());
+		assert!(parse_date("9999-12-31T23:59-00:01").is_err());
+	}
+
+	#[test]
+	fn test_format() {
+		assert_eq!(format_date(0), "1970-01-01");
+		assert_eq!(format_date(-1), "1969-12-31T23:59:59.999Z");
+		assert_eq!(
+			format_date(20_744 * MILLIS_PER_DAY + 12 * MILLIS_PER_HOUR),
+			"2026-10-18T12:00:00Z"
+		);
+		assert_eq!(format_duration(0), "0s");
+		assert_eq!(format_duration(3 * MILLIS_PER_DAY + 4 * MILLIS_PER_HOUR), "3d 4h");
+		assert_eq!(format_duration(-90 * MILLIS_PER_MINUTE), "-1h 30min");
+		assert_eq!(format_duration(1_500), "1s 500ms");
+	}
+}
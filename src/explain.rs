@@ -0,0 +1,201 @@
+// shows the parse tree of an expression, and its steps, e.g. "6 * 9 → 54"
+
+use crate::ast::*;
+use crate::value::*;
+
+fn literal_value(lit: &Literal) -> Value {
+	match lit {
+		Literal::Date(millis) => Value::Date(*millis),
+		Literal::Duration(millis) => Value::Duration(*millis),
+		Literal::Integer(n) => Value::Integer(*n),
+		Literal::Number(n) => Value::Number(*n),
+		Literal::String(str) => Value::String(str.clone()),
+	}
+}
+
+// the label of an expression in the tree, along with its operands
+fn node<'a>(expr: &'a Expression, format: &Format) -> (String, Vec<&'a Expression>) {
+	match expr {
+		Expression::Assignment(a) => (format!("{} =", a.var.name), vec![a.right.as_ref()]),
+		Expression::Binary(b) => (b.op.symbol().to_string(), vec![b.left.as_ref(), b.right.as_ref()]),
+		Expression::Call(c) => (format!("{}()", c.name), c.params.iter().collect()),
+		Expression::Literal(l) => (format.value(&literal_value(l)), vec![]),
+		Expression::Result(ResultRef::Latest) => ("$$".to_string(), vec![]),
+		Expression::Result(ResultRef::Number(n)) => (format!("${}", n), vec![]),
+		Expression::Result(ResultRef::Offset(offset)) => ("ans[]".to_string(), vec![offset.as_ref()]),
+		Expression::Unary(u) => {
+			let label = match u.op {
+				UnaryOp::Factorial => "! (factorial)".to_string(),
+				ref op => op.symbol().to_string(),
+			};
+			(label, vec![u.right.as_ref()])
+		}
+		Expression::Variable(v) => (v.name.clone(), vec![]),
+	}
+} // node
+
+fn push_tree(out: &mut String, expr: &Expression, format: &Format, indent: &str) {
+	let operands = node(expr, format).1;
+
+	for (i, operand) in operands.iter().enumerate() {
+		let last = i == operands.len() - 1;
+		let (branch, more) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+
+		out.push_str(&format!("{}{}{}\n", indent, branch, node(operand, format).0));
+		push_tree(out, operand, format, &format!("{}{}", indent, more));
+	}
+}
+
+// one node on each line, with operands indented under their operator
+pub fn tree(expr: &Expression, format: &Format) -> String {
+	let mut out = node(expr, format).0;
+	out.push('\n');
+	push_tree(&mut out, expr, format, "");
+
+	out
+}
+
+// operators and negative values are put in parens when they are operands, so
+// the grouping is always shown
+fn operand((text, grouped): (String, bool)) -> String {
+	if grouped {
+		format!("({})", text)
+	} else {
+		text
+	}
+}
+
+// returns the text of the expression, where the first `reduced` expressions in
+// the trace have been replaced by their values, and whether it needs parens as
+// an operand; next counts the expressions in the order the runner reduces them,
+// which is each one's operands before itself
+fn render(expr: &Expression, trace: &[Value], reduced: usize, next: &mut usize, format: &Format) -> (String, bool) {
+	let operands: Vec<(String, bool)> = node(expr, format)
+		.1
+		.into_iter()
+		.map(|e| render(e, trace, reduced, next, format))
+		.collect();
+
+	let index = *next;
+	*next += 1;
+	if index < reduced {
+		let text = format.value(&trace[index]);
+		let grouped = text.starts_with('-');
+		return (text, grouped);
+	}
+
+	let mut operands = operands.into_iter();
+	let mut take = || operands.next().unwrap_or_default();
+
+	match expr {
+		Expression::Assignment(a) => (format!("{} = {}", a.var.name, take().0), true),
+		Expression::Binary(b) => {
+			let (l, r) = (operand(take()), operand(take()));
+			(format!("{} {} {}", l, b.op.symbol(), r), true)
+		}
+		Expression::Call(c) => {
+			let params: Vec<String> = c.params.iter().map(|_| take().0).collect();
+			(format!("{}({})", c.name, params.join(", ")), false)
+		}
+		Expression::Result(ResultRef::Offset(_)) => (format!("ans[{}]", take().0), false),
+		Expression::Unary(u) => match u.op {
+			UnaryOp::Factorial => (format!("{}!", operand(take())), true),
+			ref op => (format!("{}{}", op.symbol(), operand(take())), true),
+		},
+		_ => (node(expr, format).0, false),
+	}
+} // render
+
+// steps which look the same, like reducing a literal, are skipped
+pub fn steps(expr: &Expression, trace: &[Value], format: &Format) -> Vec<String> {
+	let mut steps: Vec<String> = vec![];
+
+	for reduced in 0..=trace.len() {
+		let (text, _) = render(expr, trace, reduced, &mut 0, format);
+		if steps.last() != Some(&text) {
+			steps.push(text);
+		}
+	}
+
+	steps
+}
+
+pub fn explain(expr: &Expression, trace: &[Value], format: &Format) -> String {
+	format!("{}{}\n", tree(expr, format), steps(expr, trace, format).join(" → "))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::explain::*;
+	use crate::parsing::*;
+	use crate::running::*;
+
+	fn run(runner: &mut Runner, input: &str) -> (Vec<String>, Result<Value, String>) {
+		let expr = match parse(input) {
+			Some(Ast::Expression(expr)) => expr,
+			ast => panic!("Unexpected Ast for input \"{}\": {:?}", input, ast),
+		};
+
+		let (trace, result) = runner.traced(|runner| runner.evaluate(&expr));
+		(steps(&expr, &trace, &Format::default()), result)
+	}
+
+	fn expect(input: &str, expected: &[&str]) {
+		let (steps, result) = run(&mut Runner::new(), input);
+		assert!(result.is_ok());
+		assert_eq!(steps, expected);
+	}
+
+	#[test]
+	fn explain_steps() {
+		expect("6*3**2", &["6 * (3 ** 2)", "6 * 9", "54"]);
+		expect("(1 + 2) * (3 + 4)", &["(1 + 2) * (3 + 4)", "3 * (3 + 4)", "3 * 7", "21"]);
+		expect("1 - 2 - 3", &["(1 - 2) - 3", "(-1) - 3", "-4"]);
+		expect("-3 ** 2", &["(-3) ** 2", "9"]);
+		expect("gcd(2 + 2, 6)! / 2", &["(gcd(2 + 2, 6)!) / 2", "(gcd(4, 6)!) / 2", "(2!) / 2", "2 / 2", "1"]);
+		expect("42", &["42"]);
+
+		let mut runner = Runner::new();
+		let (steps, result) = run(&mut runner, "x = 2 * pi / pi");
+		assert_eq!(
+			steps,
+			[
+				"x = (2 * pi) / pi",
+				"x = (2 * 3.141592653589793) / pi",
+				"x = 6.283185307179586 / pi",
+				"x = 6.283185307179586 / 3.141592653589793",
+				"x = 2",
+				"2",
+			]
+		);
+		assert_eq!(result, Ok(Value::Number(2f64)));
+
+		let (steps, result) = run(&mut runner, "x + 1 / (x - 2)");
+		assert_eq!(
+			steps,
+			["x + (1 / (x - 2))", "2 + (1 / (x - 2))", "2 + (1 / (2 - 2))", "2 + (1 / 0)"]
+		);
+		assert_eq!(result, Err("Cannot divide by zero".to_string()));
+	}
+
+	#[test]
+	fn explain_tree() {
+		let expr = match parse("x = 6*3**2 + fact(3)") {
+			Some(Ast::Expression(expr)) => expr,
+			ast => panic!("Unexpected Ast: {:?}", ast),
+		};
+
+		let lines = [
+			"x =",
+			"└── +",
+			"    ├── *",
+			"    │   ├── 6",
+			"    │   └── **",
+			"    │       ├── 3",
+			"    │       └── 2",
+			"    └── fact()",
+			"        └── 3",
+		];
+		assert_eq!(tree(&expr, &Format::default()), lines.join("\n") + "\n");
+	}
+}
@@ -0,0 +1,211 @@
+// help is generated from the tables which define builtins, so it cannot drift
+
+use crate::ast::*;
+use crate::builtins;
+use crate::parsing;
+use crate::running::*;
+
+// (usage, description), from the highest precedence to the lowest
+static OPERATORS: &[(&str, &str)] = &[
+	("( )", "parens"),
+	("!", "factorial (suffix)"),
+	("- !", "negate, bitwise NOT"),
+	("**", "exponentiation"),
+	("* / %", "multiplication, division, modulo"),
+	("+ -", "addition, subtraction"),
+	("++ --", "increment, decrement (suffix)"),
+	("<< >>", "left shift, right shift"),
+	("&", "bitwise AND"),
+	("^", "bitwise XOR"),
+	("|", "bitwise OR"),
+	("=", "variable assignment"),
+	("+= -= *= /= %= **= &= |= ^= <<= >>=", "compound assignment"),
+];
+
+static LITERALS: &[(&str, &str)] = &[
+	("42 1.5 1_000", "decimal numbers"),
+	("0b101 0o17 0d9 0x1f", "binary, octal, decimal and hexadecimal numbers"),
+	("2026-10-18", "date, at midnight UTC"),
+	("2026-10-18T12:00:30.5Z", "date and time; Z, +HH:MM or -HH:MM sets the UTC offset"),
+	("90min 3d 4h", "durations in w, d, h, min, s or ms; adjacent ones are summed"),
+	("$n $$ ans[-k]", "result n, the latest result, and the kth latest result"),
+];
+
+// variables which are not in the table of constants
+static VARIABLES: &[(&str, &str)] = &[
+	("ans", "Result of the previous expression"),
+	("now", "Current date and time"),
+	("today", "Current date, at midnight"),
+];
+
+static COMMANDS: &[(&str, &str)] = &[
+	("autosave [on/off]", "show or toggle saving the session on exit"),
+	("clear", "delete all user variables and constants"),
+	("const name = expr", "declare a constant, which cannot be reassigned or deleted"),
+	("delete name", "delete a variable"),
+	("exit", "exit the REPL"),
+	("explain expr", "show how expr is grouped, and each step of running it"),
+	("funcs", "list all functions with their parameters"),
+	("help [name]", "describe everything, or one function, constant, operator or command"),
+	("load [file]", "run each line of file, restoring a saved session"),
+	("quit", "alias for exit"),
+	("reset", "restore the session to its startup state"),
+	("save [file]", "save user variables, constants and results to file"),
+	("set angle deg/rad", "read and return angles in degrees or radians"),
+	("set mode rpn/infix", "switch between RPN and infix input"),
+	("set number auto/fixed/sci", "print numbers as needed, with fixed decimals, or in scientific notation"),
+	("set precision N/auto", "print numbers with up to N digits after the decimal point"),
+	("set radix 2/8/10/16", "print integers in binary, octal, decimal or hexadecimal"),
+	("vars", "list the variables in each scope with their values"),
+];
+
+fn push_table<S: AsRef<str>>(out: &mut String, title: &str, rows: &[(S, &str)]) {
+	let width = rows.iter().map(|(usage, _)| usage.as_ref().chars().count()).max().unwrap_or(0);
+
+	out.push_str(title);
+	out.push_str(":\n");
+	for (usage, description) in rows {
+		out.push_str(&format!("  {:width$}  {}\n", usage.as_ref(), description, width = width));
+	}
+}
+
+// runs an example against a fresh session, so help always shows real results
+fn run_example(input: &str) -> String {
+	let result = match parsing::try_parse(input) {
+		Ok(Some(Ast::Expression(expr))) => Runner::new().run_expression(&expr),
+		Ok(_) => Err("Not an expression".to_string()),
+		Err(msg) => Err(msg),
+	};
+
+	match result {
+		Ok(value) => format!("{} = {}", input, value),
+		Err(msg) => format!("{}: {}", input, msg),
+	}
+}
+
+fn overview() -> String {
+	let mut out = String::new();
+
+	push_table(&mut out, "Operators, from the highest precedence to the lowest", OPERATORS);
+	out.push('\n');
+	push_table(&mut out, "Literals", LITERALS);
+	out.push('\n');
+
+	let mut variables: Vec<(String, &str)> = VARIABLES
+		.iter()
+		.map(|(name, description)| (name.to_string(), *description))
+		.collect();
+	variables.extend(
+		CONSTANTS
+			.iter()
+			.map(|(name, _, description)| (name.to_string(), *description)),
+	);
+	push_table(&mut out, "Variables and constants", &variables);
+	out.push('\n');
+
+	let functions: Vec<(String, &str)> = builtins::BUILTINS
+		.iter()
+		.map(|b| (b.signature(), b.description))
+		.collect();
+	push_table(&mut out, "Functions", &functions);
+	out.push('\n');
+
+	push_table(&mut out, "Commands", COMMANDS);
+	out.push('\n');
+	out.push_str("Run \"help <name>\" for more about one of them, e.g. help gcd\n");
+
+	out
+} // overview
+
+fn describe_builtin(builtin: &builtins::Builtin) -> String {
+	let mut out = format!("{}\n  {}\n", builtin.signature(), builtin.description);
+
+	if !builtin.examples.is_empty() {
+		out.push_str("\nExamples:\n");
+		for example in builtin.examples {
+			out.push_str(&format!("  {}\n", run_example(example)));
+		}
+	}
+
+	out
+}
+
+// describes everything when there is no topic
+pub fn help(topic: Option<&str>) -> Result<String, String> {
+	let topic = match topic {
+		Some(topic) => topic,
+		None => return Ok(overview()),
+	};
+
+	if let Some(builtin) = builtins::find(topic) {
+		return Ok(describe_builtin(builtin));
+	}
+
+	if let Some((name, value, description)) = CONSTANTS.iter().find(|(name, ..)| *name == topic) {
+		return Ok(format!("{} = {}\n  {}\n", name, value, description));
+	}
+
+	// operators and commands are looked up by any of the words in their usage
+	let tables = [VARIABLES, OPERATORS, COMMANDS];
+	for (usage, description) in tables.iter().flat_map(|table| table.iter()) {
+		if usage.split_whitespace().any(|word| word == topic) {
+			return Ok(format!("{}\n  {}\n", usage, description));
+		}
+	}
+
+	Err(format!("No help for \"{}\"; run \"help\" to list everything", topic))
+} // help
+
+#[cfg(test)]
+mod tests {
+	use crate::help::*;
+
+	#[test]
+	fn help_topics() {
+		assert_eq!(
+			help(Some("gcd")),
+			Ok("gcd(n, ...)\n  Returns the greatest common divisor of all arguments\n\nExamples:\n  gcd(12, 18, 27) = 3\n".to_string())
+		);
+		assert_eq!(
+			help(Some("phys.c")),
+			Ok("phys.c = 299792458\n  Speed of light in vacuum (m/s)\n".to_string())
+		);
+		assert_eq!(
+			help(Some("%")),
+			Ok("* / %\n  multiplication, division, modulo\n".to_string())
+		);
+		assert_eq!(
+			help(Some("load")),
+			Ok("load [file]\n  run each line of file, restoring a saved session\n".to_string())
+		);
+		assert_eq!(
+			help(Some("foo")),
+			Err("No help for \"foo\"; run \"help\" to list everything".to_string())
+		);
+
+		let overview = help(None).unwrap();
+		for builtin in builtins::BUILTINS {
+			assert!(overview.contains(&builtin.signature()));
+		}
+	}
+
+	#[test]
+	fn examples_run() {
+		// set by shells, but not necessarily where tests run
+		if std::env::var("SHLVL").is_err() {
+			std::env::set_var("SHLVL", "1");
+		}
+
+		for builtin in builtins::BUILTINS {
+			assert!(!builtin.examples.is_empty(), "{} has no examples", builtin.name);
+			for example in builtin.examples {
+				let shown = run_example(example);
+				assert!(
+					shown.starts_with(&format!("{} = ", example)),
+					"Example failed: {}",
+					shown
+				);
+			}
+		}
+	}
+}
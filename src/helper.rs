@@ -0,0 +1,239 @@
+// connects the REPL's line editor to completion, highlighting and previews
+
+use crate::ast::*;
+use crate::completion::*;
+use crate::highlighting;
+use crate::parsing;
+use crate::rpn::{self, Stack};
+use crate::running::*;
+use rustyline::completion::Completer;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::{Hint, Hinter};
+use rustyline::validate::Validator;
+use rustyline::{
+	Cmd, ConditionalEventHandler, Context, Event, EventContext, Helper, Movement, RepeatCount,
+};
+use std::borrow::Cow;
+use std::env;
+use std::io::{self, IsTerminal};
+
+pub fn use_color() -> bool {
+	let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+	!no_color && io::stdout().is_terminal()
+}
+
+pub struct ReplHint(String);
+
+impl Hint for ReplHint {
+	fn display(&self) -> &str {
+		&self.0
+	}
+
+	// hints are informational only, and never inserted into the line
+	fn completion(&self) -> Option<&str> {
+		None
+	}
+}
+
+pub struct ReplHelper {
+	completer: NameCompleter,
+	runner: Runner,
+	pending: String,
+	mode: Mode,
+	stack: Stack,
+	color: bool,
+}
+
+impl ReplHelper {
+	pub fn new(color: bool) -> ReplHelper {
+		ReplHelper {
+			completer: NameCompleter::new(),
+			runner: Runner::new(),
+			pending: String::new(),
+			mode: Mode::Infix,
+			stack: Stack::new(),
+			color,
+		}
+	}
+
+	// pending holds any unfinished lines before the one being edited
+	pub fn update_session(&mut self, runner: &Runner, pending: &str) {
+		self.completer.update_vars(runner);
+		self.runner = runner.clone();
+		self.pending = pending.to_string();
+	}
+
+	pub fn update_stack(&mut self, mode: Mode, stack: &Stack) {
+		self.mode = mode;
+		self.stack = stack.clone();
+	}
+
+	// unfinished lines are included, so the line being edited is seen in context
+	fn with_pending<'l>(&self, line: &'l str) -> Cow<'l, str> {
+		if self.pending.is_empty() {
+			Cow::Borrowed(line)
+		} else {
+			Cow::Owned(format!("{}\n{}", self.pending, line))
+		}
+	}
+
+	// evaluates the line against a copy of the session, so assignments in the
+	// preview never change the real session
+	fn preview(&self, line: &str) -> Option<String> {
+		if self.mode == Mode::Rpn {
+			return self.preview_rpn(line);
+		}
+
+		// incomplete input is expected while typing, so it is not an error
+		if parsing::is_incomplete(line) {
+			return None;
+		}
+
+		match parsing::try_parse(line) {
+			Ok(Some(Ast::Expression(expr))) => match self.runner.clone().run_expression(&expr) {
+				Ok(value) => Some(format!("  = {}", value)),
+				Err(msg) => Some(format!("  {}", msg)),
+			},
+			Ok(_) => None,
+			Err(msg) => Some(format!("  {}", msg)),
+		}
+	}
+
+	// shows what would be on top of the stack after the line
+	fn preview_rpn(&self, line: &str) -> Option<String> {
+		if rpn::command(line).is_some() {
+			return None;
+		}
+
+		let mut stack = self.stack.clone();
+		match stack.run_line(&mut self.runner.clone(), line) {
+			Ok(_) => stack.top().map(|v| format!("  = {}", v)),
+			Err(msg) => Some(format!("  {}", msg)),
+		}
+	}
+} // ReplHelper
+
+impl Completer for ReplHelper {
+	type Candidate = String;
+
+	fn complete(
+		&self,
+		line: &str,
+		pos: usize,
+		_ctx: &Context<'_>,
+	) -> rustyline::Result<(usize, Vec<String>)> {
+		Ok(self.completer.complete(line, pos))
+	}
+}
+
+impl Hinter for ReplHelper {
+	type Hint = ReplHint;
+
+	fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<ReplHint> {
+		if line.trim().is_empty() {
+			return None;
+		}
+
+		let input = self.with_pending(line);
+		let pos = pos + input.len() - line.len();
+
+		// an unfinished call cannot be evaluated, so show what it expects instead
+		let call_hint = match self.mode {
+			Mode::Infix => highlighting::call_hint(&input, pos),
+			Mode::Rpn => None,
+		};
+		let hint = match call_hint {
+			Some(sig) => format!("  {}", sig),
+			None => self.preview(&input)?,
+		};
+
+		Some(ReplHint(hint))
+	}
+}
+
+impl Highlighter for ReplHelper {
+	fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+		if !self.color {
+			return Cow::Borrowed(line);
+		}
+
+		let input = self.with_pending(line);
+		let start = input.len() - line.len();
+		Cow::Owned(highlighting::highlight(&input, start, |name| {
+			self.completer.is_var(name) || (self.mode == Mode::Rpn && rpn::is_word(name))
+		}))
+	}
+
+	fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+		if !self.color {
+			return Cow::Borrowed(hint);
+		}
+
+		Cow::Owned(format!("{}{}\x1b[0m", highlighting::HINT, hint))
+	}
+
+	fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+		self.color && kind != CmdKind::MoveCursor
+	}
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+// Ctrl-C clears the line, or exits when it is empty
+pub struct CancelHandler;
+
+impl ConditionalEventHandler for CancelHandler {
+	fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+		if ctx.line().is_empty() {
+			Some(Cmd::Interrupt)
+		} else {
+			Some(Cmd::Kill(Movement::WholeBuffer))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::helper::*;
+	use rustyline::history::DefaultHistory;
+
+	#[test]
+	fn preview_line() {
+		let mut helper = ReplHelper::new(false);
+		let mut runner = Runner::new();
+		if let Some(Ast::Expression(expr)) = parsing::parse("x = 4") {
+			runner.run_expression(&expr).unwrap();
+		}
+		helper.update_session(&runner, "");
+
+		assert_eq!(helper.preview("x * 2"), Some("  = 8".to_string()));
+		assert_eq!(helper.preview("x = 5"), Some("  = 5".to_string()));
+		assert_eq!(helper.preview("x"), Some("  = 4".to_string()));
+		assert_eq!(
+			helper.preview("y"),
+			Some("  Variable \"y\" is undefined".to_string())
+		);
+		assert_eq!(helper.preview("x +"), None);
+		assert_eq!(
+			helper.preview("x $"),
+			Some("  Unexpected character '$' (line 1, column 3)".to_string())
+		);
+		assert_eq!(helper.preview("vars"), None);
+
+		helper.update_stack(Mode::Rpn, &Stack::new());
+		assert_eq!(helper.preview("x 3 +"), Some("  = 7".to_string()));
+		assert_eq!(
+			helper.preview("x +"),
+			Some("  '+' expects 2 values on the stack, but found 1".to_string())
+		);
+		assert_eq!(helper.preview("vars"), None);
+
+		helper.update_stack(Mode::Infix, &Stack::new());
+		helper.update_session(&runner, "x * (");
+		let history = DefaultHistory::new();
+		let hint = helper.hint("3)", 2, &Context::new(&history));
+		assert_eq!(hint.map(|h| h.0), Some("  = 12".to_string()));
+	}
+}
@@ -0,0 +1,273 @@
+// only ANSI escapes are inserted, so the display width of the line is kept
+
+use crate::builtins;
+use crate::parsing;
+use crate::scanning::*;
+
+const RESET: &str = "\x1b[0m";
+const DECIMAL: &str = "\x1b[36m";
+const BINARY: &str = "\x1b[32m";
+const OCTAL: &str = "\x1b[34m";
+const HEX: &str = "\x1b[35m";
+const TIME: &str = "\x1b[96m";
+const STRING: &str = "\x1b[92m";
+const OPERATOR: &str = "\x1b[33m";
+const FUNCTION: &str = "\x1b[1m";
+const KEYWORD: &str = "\x1b[1;34m";
+const UNKNOWN: &str = "\x1b[4;31m";
+const ERROR: &str = "\x1b[1;31m";
+pub const HINT: &str = "\x1b[90m";
+
+fn number_style(prefix: &str) -> &'static str {
+	match prefix {
+		"0b" => BINARY,
+		"0o" => OCTAL,
+		"0x" => HEX,
+		_ => DECIMAL,
+	}
+}
+
+fn is_assign(token_type: &TokenType) -> bool {
+	matches!(
+		token_type,
+		TokenType::Equal
+			| TokenType::AmpersandEqual
+			| TokenType::CaretEqual
+			| TokenType::ForwardSlashEqual
+			| TokenType::LeftAngleBracketX2Equal
+			| TokenType::MinusEqual
+			| TokenType::PercentEqual
+			| TokenType::PipeEqual
+			| TokenType::PlusEqual
+			| TokenType::RightAngleBracketX2Equal
+			| TokenType::StarEqual
+			| TokenType::StarX2Equal
+	)
+}
+
+// maps the scanner's line and column to byte offsets in the input
+fn byte_range(line: &str, line_starts: &[usize], t: &Token) -> (usize, usize) {
+	let line_start = line_starts[t.line as usize - 1];
+	let mut chars = line[line_start..]
+		.char_indices()
+		.map(|(i, _)| line_start + i)
+		.chain(std::iter::once(line.len()))
+		.skip(t.column as usize - 1);
+
+	let start = chars.next().unwrap_or(line.len());
+	let end = chars.nth(t.length as usize - 1).unwrap_or(line.len());
+
+	(start, end.max(start))
+}
+
+fn identifier_style<F>(tokens: &[Token], i: usize, str: &str, is_var: &F) -> Option<&'static str>
+where
+	F: Fn(&str) -> bool,
+{
+	let prev = i.checked_sub(1).map(|i| &tokens[i].token_type);
+	let next = tokens.get(i + 1).map(|t| &t.token_type);
+
+	if next == Some(&TokenType::LeftParen) {
+		return Some(if builtins::find(str).is_some() {
+			FUNCTION
+		} else {
+			UNKNOWN
+		});
+	}
+
+	if i == 0 && parsing::KEYWORDS.contains(&str) {
+		return Some(KEYWORD);
+	}
+
+	// names being defined are allowed to be unknown
+	let defining = next.is_some_and(is_assign)
+		|| matches!(prev, Some(TokenType::Identifier { str }) if i == 1 && str == "const");
+
+	if defining || is_var(str) {
+		None
+	} else {
+		Some(UNKNOWN)
+	}
+}
+
+// returns line[start..] colored, where lines before start are only context
+pub fn highlight<F>(line: &str, start: usize, is_var: F) -> String
+where
+	F: Fn(&str) -> bool,
+{
+	let mut line_starts = vec![0];
+	line_starts.extend(line.match_indices('\n').map(|(i, _)| i + 1));
+
+	let mut scanner = Scanner::new(line);
+	let tokens: Vec<Token> = scanner.by_ref().collect();
+	let scanned_all = scanner.take_errors().is_empty();
+
+	// parens still open may yet be closed, so only extra closing parens are errors
+	let mut styles: Vec<Option<&str>> = vec![None; tokens.len()];
+	let mut open_parens = 0;
+	for (i, t) in tokens.iter().enumerate() {
+		styles[i] = match t.token_type {
+			TokenType::Comma
+			| TokenType::LeftBracket
+			| TokenType::NewLine
+			| TokenType::ResultRef { .. }
+			| TokenType::RightBracket => None,
+			TokenType::Date { .. } | TokenType::Duration { .. } => Some(TIME),
+			TokenType::Identifier { ref str } => {
+				// file names and help topics are not tokens, so leave them as they are
+				if i == 0 && start == 0 && matches!(str.as_str(), "help" | "load" | "save") {
+					let (start, end) = byte_range(line, &line_starts, t);
+					if !parsing::continues_expression(str, &line[end..]) {
+						return format!(
+							"{}{}{}{}{}",
+							&line[..start],
+							KEYWORD,
+							&line[start..end],
+							RESET,
+							&line[end..]
+						);
+					}
+				}
+
+				identifier_style(&tokens, i, str, &is_var)
+			}
+			TokenType::LeftParen => {
+				open_parens += 1;
+				None
+			}
+			TokenType::Number { ref prefix, .. } => Some(number_style(prefix)),
+			TokenType::String { .. } => Some(STRING),
+			TokenType::RightParen if open_parens > 0 => {
+				open_parens -= 1;
+				None
+			}
+			TokenType::RightParen => Some(ERROR),
+			_ => Some(OPERATOR),
+		};
+	}
+
+	let mut out = String::with_capacity(line.len() * 2);
+	let mut pos = start;
+	for (t, style) in tokens.iter().zip(styles) {
+		let (t_start, t_end) = byte_range(line, &line_starts, t);
+		if t_start < start {
+			continue;
+		}
+
+		out.push_str(&line[pos..t_start]);
+		match style {
+			Some(style) => {
+				out.push_str(style);
+				out.push_str(&line[t_start..t_end]);
+				out.push_str(RESET);
+			}
+			None => out.push_str(&line[t_start..t_end]),
+		}
+		pos = t_end;
+	}
+
+	// the scanner stops at the first character it cannot scan
+	let rest = &line[pos..];
+	if !scanned_all && !rest.trim().is_empty() {
+		let trimmed = rest.trim_start();
+		out.push_str(&rest[..rest.len() - trimmed.len()]);
+		out.push_str(ERROR);
+		out.push_str(trimmed);
+		out.push_str(RESET);
+	} else {
+		out.push_str(rest);
+	}
+
+	out
+} // highlight
+
+// the signature of the innermost builtin being called at pos
+pub fn call_hint(line: &str, pos: usize) -> Option<String> {
+	let before = &line[..pos];
+	let mut depth = 0;
+
+	for (i, c) in before.char_indices().rev() {
+		match c {
+			')' => depth += 1,
+			'(' if depth > 0 => depth -= 1,
+			'(' => {
+				let name_start = before[..i]
+					.char_indices()
+					.rev()
+					.take_while(|(_, c)| c.is_alphanumeric() || *c == '_' || *c == '.')
+					.last()
+					.map(|(i, _)| i)?;
+
+				return builtins::find(&before[name_start..i]).map(|b| b.signature());
+			}
+			_ => {}
+		}
+	}
+
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::highlighting::*;
+
+	fn is_var(name: &str) -> bool {
+		name == "x"
+	}
+
+	#[test]
+	fn highlight_tokens() {
+		assert_eq!(
+			highlight("x + 0x1f", 0, is_var),
+			format!("x {}+{} {}0x1f{}", OPERATOR, RESET, HEX, RESET)
+		);
+		assert_eq!(
+			highlight("flor(2.5)", 0, is_var),
+			format!("{}flor{}({}2.5{})", UNKNOWN, RESET, DECIMAL, RESET)
+		);
+		assert_eq!(
+			highlight("floor(y))", 0, is_var),
+			format!("{}floor{}({}y{}){}){}", FUNCTION, RESET, UNKNOWN, RESET, ERROR, RESET)
+		);
+		assert_eq!(
+			highlight("const y = 2h", 0, is_var),
+			format!(
+				"{}const{} y {}={} {}2h{}",
+				KEYWORD, RESET, OPERATOR, RESET, TIME, RESET
+			)
+		);
+		assert_eq!(
+			highlight("x $ 1", 0, is_var),
+			format!("x {}$ 1{}", ERROR, RESET)
+		);
+		assert_eq!(
+			highlight("floor(\n1))", 7, is_var),
+			format!("{}1{}){}){}", DECIMAL, RESET, ERROR, RESET)
+		);
+		assert_eq!(
+			highlight("x =", 0, is_var),
+			format!("x {}={}", OPERATOR, RESET)
+		);
+		assert_eq!(
+			highlight("env(\"N\")", 0, is_var),
+			format!("{}env{}({}\"N\"{})", FUNCTION, RESET, STRING, RESET)
+		);
+		assert_eq!(
+			highlight("save ~/a b", 0, is_var),
+			format!("{}save{} ~/a b", KEYWORD, RESET)
+		);
+		assert_eq!(
+			highlight("save = 2", 0, is_var),
+			format!("{}save{} {}={} {}2{}", KEYWORD, RESET, OPERATOR, RESET, DECIMAL, RESET)
+		);
+	}
+
+	#[test]
+	fn hint_calls() {
+		assert_eq!(call_hint("modpow(2, ", 10), Some("modpow(b, e, m)".to_string()));
+		assert_eq!(call_hint("gcd(abs(-4), ", 13), Some("gcd(n, ...)".to_string()));
+		assert_eq!(call_hint("abs(2) + ", 9), None);
+		assert_eq!(call_hint("(1 + ", 5), None);
+		assert_eq!(call_hint("flor(", 5), None);
+	}
+}
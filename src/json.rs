@@ -0,0 +1,266 @@
+// reports of what each input did, as one JSON object per line
+
+use serde_json::{json, Map, Value as Json};
+use std::path::Path;
+
+use crate::running::*;
+use crate::scanning::SyntaxError;
+use crate::value::*;
+
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+	// command, const, delete, empty, expression, rpn or set_result
+	pub kind: &'static str,
+	pub name: Option<String>,
+	pub value: Option<Value>,
+	pub number: Option<usize>,
+	pub output: String,
+}
+
+impl Outcome {
+	pub fn new(kind: &'static str) -> Outcome {
+		Outcome {
+			kind,
+			name: None,
+			value: None,
+			number: None,
+			output: String::new(),
+		}
+	}
+
+	pub fn with_name(self, name: String) -> Outcome {
+		Outcome {
+			name: Some(name),
+			..self
+		}
+	}
+
+	pub fn with_value(self, value: Option<Value>) -> Outcome {
+		Outcome { value, ..self }
+	}
+
+	pub fn with_number(self, number: usize) -> Outcome {
+		Outcome {
+			number: Some(number),
+			..self
+		}
+	}
+
+	pub fn with_output(self, output: String) -> Outcome {
+		Outcome { output, ..self }
+	}
+
+	pub fn shows_value(&self) -> bool {
+		matches!(self.kind, "expression" | "rpn")
+	}
+} // Outcome
+
+pub enum Source<'a> {
+	// a script which could not be read
+	File(&'a Path),
+	Input(&'a str),
+	// a statement in a script, the line it starts on, and its text
+	Script(&'a Path, u32, &'a str),
+}
+
+pub fn value(v: &Value, format: &Format) -> Json {
+	match v {
+		Value::Date(millis) | Value::Duration(millis) => json!({
+			"type": v.type_name(),
+			"millis": millis,
+			"text": format.value(v),
+		}),
+		Value::List(items) => json!({
+			"type": "list",
+			"items": items.iter().map(|item| value(item, format)).collect::<Vec<Json>>(),
+			"text": format.value(v),
+		}),
+		Value::String(str) => json!({
+			"type": "string",
+			"value": str,
+			"text": v.to_string(),
+		}),
+		// JSON readers mostly use f64s, so the exact integer is only in the text
+		Value::Integer(n) => json!({
+			"type": "number",
+			"value": *n as f64,
+			"text": format.value(v),
+		}),
+		// NaN and infinities have no JSON number, so they are null
+		Value::Number(n) => json!({
+			"type": "number",
+			"value": n,
+			"text": format.value(v),
+		}),
+	}
+}
+
+// inner scopes shadow outer ones
+pub fn vars(runner: &Runner, format: &Format) -> Json {
+	let mut names: Vec<&String> = runner.scopes().iter().skip(1).flat_map(|s| s.keys()).collect();
+	names.sort();
+	names.dedup();
+
+	let mut out = Map::new();
+	for name in names {
+		let scope = runner.scopes().iter().rev().find(|s| s.contains_key(name));
+		if let Some(scope) = scope {
+			out.insert(name.clone(), value(&scope[name], format));
+		}
+	}
+
+	Json::Object(out)
+}
+
+pub fn error(e: &SyntaxError) -> Json {
+	let (line, column) = match e.position {
+		Some((line, column)) => (Some(line), Some(column)),
+		None => (None, None),
+	};
+
+	json!({
+		"message": e.message,
+		"line": line,
+		"column": column,
+	})
+}
+
+pub fn record(
+	source: &Source,
+	result: &Result<Outcome, SyntaxError>,
+	runner: &Runner,
+	format: &Format,
+) -> String {
+	let mut out = Map::new();
+
+	let (input, file, line) = match source {
+		Source::File(path) => (None, Some(path.display().to_string()), None),
+		Source::Input(input) => (Some(input.to_string()), None, None),
+		Source::Script(path, line, text) => {
+			(Some(text.to_string()), Some(path.display().to_string()), Some(*line))
+		}
+	};
+	out.insert("input".to_string(), json!(input));
+	if file.is_some() {
+		out.insert("file".to_string(), json!(file));
+		out.insert("line".to_string(), json!(line));
+	}
+
+	match result {
+		Ok(outcome) => {
+			out.insert("kind".to_string(), json!(outcome.kind));
+			out.insert("name".to_string(), json!(outcome.name));
+			out.insert("value".to_string(), outcome.value.as_ref().map_or(Json::Null, |v| value(v, format)));
+			out.insert("number".to_string(), json!(outcome.number));
+			out.insert("output".to_string(), json!(outcome.output));
+			out.insert("error".to_string(), Json::Null);
+		}
+		// failed inputs have the same keys, with nothing in them
+		Err(e) => {
+			for key in ["kind", "name", "value", "number"] {
+				out.insert(key.to_string(), Json::Null);
+			}
+			out.insert("output".to_string(), json!(""));
+			out.insert("error".to_string(), error(e));
+		}
+	}
+	out.insert("vars".to_string(), vars(runner, format));
+
+	Json::Object(out).to_string()
+} // record
+
+#[cfg(test)]
+mod tests {
+	use crate::json::*;
+	use crate::parsing;
+
+	fn parse(record: &str) -> Json {
+		serde_json::from_str(record).unwrap()
+	}
+
+	#[test]
+	fn json_records() {
+		let mut runner = Runner::new();
+		let ast = parsing::try_parse("x = 2 ** 3").unwrap().unwrap();
+		if let crate::ast::Ast::Expression(expr) = ast {
+			runner.run_numbered(&expr).unwrap();
+		}
+
+		let outcome = Outcome::new("expression")
+			.with_name("x".to_string())
+			.with_value(Some(Value::Number(8f64)))
+			.with_number(1);
+		let number = json!({"type": "number", "value": 8.0, "text": "8"});
+		assert_eq!(
+			parse(&record(&Source::Input("x = 2 ** 3"), &Ok(outcome), &runner, &Format::default())),
+			json!({
+				"input": "x = 2 ** 3",
+				"kind": "expression",
+				"name": "x",
+				"value": number,
+				"number": 1,
+				"output": "",
+				"error": null,
+				"vars": {"ans": number, "x": number},
+			})
+		);
+
+		let e = parsing::try_parse_located("1 +* 2").unwrap_err();
+		assert_eq!(
+			parse(&record(&Source::Input("1 +* 2"), &Err(e), &Runner::new(), &Format::default())),
+			json!({
+				"input": "1 +* 2",
+				"kind": null,
+				"name": null,
+				"value": null,
+				"number": null,
+				"output": "",
+				"error": {"message": "Unexpected token 'Star'", "line": 1, "column": 4},
+				"vars": {},
+			})
+		);
+
+		assert_eq!(
+			parse(&record(&Source::Script(Path::new("a.tc"), 3, "delete y"), &Ok(Outcome::new("delete")), &runner, &Format::default())),
+			json!({
+				"input": "delete y",
+				"file": "a.tc",
+				"line": 3,
+				"kind": "delete",
+				"name": null,
+				"value": null,
+				"number": null,
+				"output": "",
+				"error": null,
+				"vars": {"ans": number, "x": number},
+			})
+		);
+
+		// script errors keep where in the file they were found
+		let (line, text, ast) = parsing::parse_script_sourced("a = 1\nb = 1 +* 2").remove(1);
+		assert_eq!(
+			parse(&record(&Source::Script(Path::new("a.tc"), line, text), &Err(ast.unwrap_err()), &runner, &Format::default())),
+			json!({
+				"input": "b = 1 +* 2",
+				"file": "a.tc",
+				"line": 2,
+				"kind": null,
+				"name": null,
+				"value": null,
+				"number": null,
+				"output": "",
+				"error": {"message": "Unexpected token 'Star'", "line": 2, "column": 8},
+				"vars": {"ans": number, "x": number},
+			})
+		);
+
+		assert_eq!(
+			value(&Value::List(vec![Value::Duration(90_000)]), &Format::default()),
+			json!({
+				"type": "list",
+				"items": [{"type": "duration", "millis": 90_000, "text": "1min 30s"}],
+				"text": "[1min 30s]",
+			})
+		);
+	}
+}
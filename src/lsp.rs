@@ -0,0 +1,564 @@
+// a language server for .tc files; each document is run again when it changes
+
+use serde_json::{json, Value as Json};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use crate::ast::*;
+use crate::builtins;
+use crate::completion::NameCompleter;
+use crate::parsing;
+use crate::running::*;
+use crate::scanning::*;
+use crate::server::{self, NewRunner, RpcError};
+use crate::value::*;
+
+const SEVERITY_ERROR: u32 = 1;
+const KIND_FUNCTION: u32 = 3;
+const KIND_VARIABLE: u32 = 6;
+const KIND_KEYWORD: u32 = 14;
+
+// line and column from 1, and length in chars
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Span {
+	line: u32,
+	column: u32,
+	length: u32,
+}
+
+struct Definition {
+	name: String,
+	span: Span,
+	value: Option<Value>,
+}
+
+fn line_of(text: &str, line: u32) -> &str {
+	text.lines().nth(line as usize - 1).unwrap_or("")
+}
+
+// lines are scanned on their own, so one which fails to scan doesn't hide the
+// tokens of the rest
+fn token_at(text: &str, column: u32) -> Option<Token> {
+	Scanner::new(text).find(|t| t.column <= column && column < t.column + t.length)
+}
+
+fn var_value<'a>(runner: &'a Runner, name: &str) -> Option<&'a Value> {
+	runner.scopes().iter().rev().find_map(|scope| scope.get(name))
+}
+
+// the span of an error at the column, or of the whole line when the column is
+// not known
+fn error_span(text: &str, line: u32, column: Option<u32>) -> Span {
+	let text = line_of(text, line);
+
+	match column {
+		Some(column) => Span {
+			line,
+			column,
+			length: token_at(text, column).map_or(1, |t| t.length),
+		},
+		None => {
+			let indent = text.chars().take_while(|c| c.is_whitespace()).count() as u32;
+			Span {
+				line,
+				column: indent + 1,
+				length: (text.trim().chars().count() as u32).max(1),
+			}
+		}
+	}
+}
+
+// the LSP counts lines from 0, and characters in UTF-16 code units
+fn position(text: &str, line: u32, column: u32) -> Json {
+	let character: usize = line_of(text, line)
+		.chars()
+		.take(column as usize - 1)
+		.map(char::len_utf16)
+		.sum();
+
+	json!({ "line": line - 1, "character": character })
+}
+
+fn range(text: &str, span: &Span) -> Json {
+	json!({
+		"start": position(text, span.line, span.column),
+		"end": position(text, span.line, span.column + span.length),
+	})
+}
+
+pub struct Document {
+	text: String,
+	errors: Vec<(Span, String)>,
+	// the value of the last expression on each line
+	values: HashMap<u32, Value>,
+	definitions: Vec<Definition>,
+	runner: Runner,
+}
+
+impl Document {
+	pub fn new(text: String, mut runner: Runner) -> Document {
+		let mut errors = vec![];
+		let mut values = HashMap::new();
+		let mut definitions = vec![];
+
+		for (line, ast) in parsing::parse_script_located(&text) {
+			let ast = match ast {
+				Ok(ast) => ast,
+				Err(e) => {
+					let (line, column) = e.position.map_or((line, None), |(l, c)| (l, Some(c)));
+					errors.push((error_span(&text, line, column), e.message));
+					continue;
+				}
+			};
+
+			let (name, result) = match ast {
+				// commands are skipped, as they are in init scripts
+				Ast::Command(_) => continue,
+				Ast::Expression(expr) => {
+					let name = match expr {
+						Expression::Assignment(ref a) => Some(a.var.name.clone()),
+						_ => None,
+					};
+					(name, runner.run_numbered(&expr).map(|(_, v)| Some(v)))
+				}
+				Ast::Statement(stmt) => {
+					let name = match stmt {
+						Statement::Const(ref a) => Some(a.var.name.clone()),
+						_ => None,
+					};
+					(name, runner.run_statement(&stmt).map(|_| None))
+				}
+			};
+
+			match result {
+				Ok(value) => {
+					if let Some(value) = value {
+						values.insert(line, value);
+					}
+
+					// the name is the first one on the line the statement starts on
+					if let Some(name) = name {
+						let token = Scanner::new(line_of(&text, line))
+							.find(|t| matches!(t.token_type, TokenType::Identifier { ref str } if *str == name));
+						let span = token.map_or(error_span(&text, line, None), |t| Span {
+							line,
+							column: t.column,
+							length: t.length,
+						});
+						let value = var_value(&runner, &name).cloned();
+						definitions.push(Definition { name, span, value });
+					}
+				}
+				Err(msg) => errors.push((error_span(&text, line, None), msg)),
+			}
+		} // for
+
+		Document {
+			text,
+			errors,
+			values,
+			definitions,
+			runner,
+		}
+	} // new
+
+	// the latest definition at or before the line, or else the first after it
+	fn definition(&self, name: &str, line: u32) -> Option<&Definition> {
+		let mut defs = self.definitions.iter().filter(|d| d.name == name);
+
+		defs.clone()
+			.rev()
+			.find(|d| d.span.line <= line)
+			.or_else(|| defs.next())
+	}
+
+	fn identifier_at(&self, line: u32, column: u32) -> Option<String> {
+		match token_at(line_of(&self.text, line), column)?.token_type {
+			TokenType::Identifier { str } => Some(str),
+			_ => None,
+		}
+	}
+
+	fn diagnostics(&self) -> Json {
+		let diagnostics: Vec<Json> = self
+			.errors
+			.iter()
+			.map(|(span, msg)| {
+				json!({
+					"range": range(&self.text, span),
+					"severity": SEVERITY_ERROR,
+					"source": env!("CARGO_PKG_NAME"),
+					"message": msg,
+				})
+			})
+			.collect();
+
+		Json::Array(diagnostics)
+	}
+
+	fn hover(&self, line: u32, column: u32, format: &Format) -> Option<String> {
+		if let Some(name) = self.identifier_at(line, column) {
+			let value = match self.definition(&name, line) {
+				Some(def) => def.value.as_ref(),
+				None => var_value(&self.runner, &name),
+			};
+
+			if let Some(value) = value {
+				return Some(format!("```\n{} = {}\n```", name, format.value(value)));
+			}
+			if let Some(builtin) = builtins::find(&name) {
+				return Some(format!("```\n{}\n```\n{}", builtin.signature(), builtin.description));
+			}
+		}
+
+		let value = self.values.get(&line)?;
+		Some(format!("```\n= {}\n```", format.value(value)))
+	}
+
+	fn complete(&self, line: u32, column: u32, format: &Format) -> Json {
+		let text = line_of(&self.text, line);
+		let pos = text
+			.char_indices()
+			.nth(column as usize - 1)
+			.map_or(text.len(), |(i, _)| i);
+
+		let mut completer = NameCompleter::new();
+		completer.update_vars(&self.runner);
+
+		let items: Vec<Json> = completer
+			.complete(text, pos)
+			.1
+			.into_iter()
+			.map(|item| match item.strip_suffix('(') {
+				Some(name) => json!({
+					"label": name,
+					"kind": KIND_FUNCTION,
+					"detail": builtins::find(name).map(|b| b.signature()),
+					"insertText": item,
+				}),
+				None if completer.is_var(&item) => json!({
+					"label": item,
+					"kind": KIND_VARIABLE,
+					"detail": var_value(&self.runner, &item).map(|v| format.value(v)),
+				}),
+				None => json!({ "label": item, "kind": KIND_KEYWORD }),
+			})
+			.collect();
+
+		Json::Array(items)
+	}
+} // Document
+
+pub struct Server<'a> {
+	new_runner: &'a NewRunner,
+	format: Format,
+	documents: HashMap<String, Document>,
+	shutdown: bool,
+	exited: bool,
+}
+
+impl<'a> Server<'a> {
+	pub fn new(new_runner: &'a NewRunner, format: Format) -> Server<'a> {
+		Server {
+			new_runner,
+			format,
+			documents: HashMap::new(),
+			shutdown: false,
+			exited: false,
+		}
+	}
+
+	// runs the new text of the document, returning its diagnostics
+	fn update(&mut self, uri: &str, text: String) -> Json {
+		let runner = (self.new_runner)().unwrap_or_else(|msg| {
+			eprintln!("{}", msg);
+			Runner::new()
+		});
+		let doc = Document::new(text, runner);
+		let diagnostics = doc.diagnostics();
+		self.documents.insert(uri.to_string(), doc);
+
+		json!({
+			"jsonrpc": "2.0",
+			"method": "textDocument/publishDiagnostics",
+			"params": { "uri": uri, "diagnostics": diagnostics },
+		})
+	}
+
+	// finds the document and the line and column of a text document position
+	// request
+	fn located(&self, params: &Json) -> Result<(&Document, u32, u32), RpcError> {
+		let invalid = || RpcError::new(server::INVALID_PARAMS, "Expected a text document position".to_string());
+
+		let uri = params["textDocument"]["uri"].as_str().ok_or_else(invalid)?;
+		let doc = self.documents.get(uri).ok_or_else(|| {
+			RpcError::new(server::INVALID_PARAMS, format!("Document \"{}\" is not open", uri))
+		})?;
+
+		let line = params["position"]["line"].as_u64().ok_or_else(invalid)? as u32 + 1;
+		let character = params["position"]["character"].as_u64().ok_or_else(invalid)? as usize;
+
+		// count chars until the UTF-16 offset is reached
+		let mut units = 0;
+		let column = 1 + line_of(&doc.text, line)
+			.chars()
+			.take_while(|c| {
+				units += c.len_utf16();
+				units <= character
+			})
+			.count() as u32;
+
+		Ok((doc, line, column))
+	}
+
+	fn call(&mut self, method: &str, params: &Json) -> Result<Json, RpcError> {
+		match method {
+			"initialize" => Ok(json!({
+				"capabilities": {
+					"textDocumentSync": 1,
+					"hoverProvider": true,
+					"completionProvider": { "triggerCharacters": ["."] },
+					"definitionProvider": true,
+				},
+				"serverInfo": {
+					"name": env!("CARGO_PKG_NAME"),
+					"version": env!("CARGO_PKG_VERSION"),
+				},
+			})),
+			"shutdown" => {
+				self.shutdown = true;
+				Ok(Json::Null)
+			}
+			"textDocument/completion" => {
+				let (doc, line, column) = self.located(params)?;
+				Ok(doc.complete(line, column, &self.format))
+			}
+			"textDocument/definition" => {
+				let (doc, line, column) = self.located(params)?;
+				let def = doc
+					.identifier_at(line, column)
+					.and_then(|name| doc.definition(&name, line));
+
+				Ok(match def {
+					Some(def) => json!({
+						"uri": params["textDocument"]["uri"],
+						"range": range(&doc.text, &def.span),
+					}),
+					None => Json::Null,
+				})
+			}
+			"textDocument/hover" => {
+				let (doc, line, column) = self.located(params)?;
+				Ok(match doc.hover(line, column, &self.format) {
+					Some(text) => json!({ "contents": { "kind": "markdown", "value": text } }),
+					None => Json::Null,
+				})
+			}
+			_ => Err(RpcError::new(
+				server::METHOD_NOT_FOUND,
+				format!("Unknown method \"{}\"", method),
+			)),
+		}
+	} // call
+
+	// handles a message without an id, returning any messages it causes
+	fn notify(&mut self, method: &str, params: &Json) -> Vec<Json> {
+		let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+		match method {
+			"exit" => {
+				self.exited = true;
+				vec![]
+			}
+			"textDocument/didOpen" => match params["textDocument"]["text"].as_str() {
+				Some(text) => vec![self.update(uri, text.to_string())],
+				None => vec![],
+			},
+			// the whole text is sent on each change
+			"textDocument/didChange" => match params["contentChanges"].as_array().and_then(|c| c.last()) {
+				Some(change) => vec![self.update(uri, change["text"].as_str().unwrap_or_default().to_string())],
+				None => vec![],
+			},
+			"textDocument/didClose" => {
+				self.documents.remove(uri);
+				vec![json!({
+					"jsonrpc": "2.0",
+					"method": "textDocument/publishDiagnostics",
+					"params": { "uri": uri, "diagnostics": [] },
+				})]
+			}
+			_ => vec![],
+		}
+	}
+
+	pub fn handle(&mut self, body: &str) -> Vec<Json> {
+		let message: Json = match serde_json::from_str(body) {
+			Ok(message) => message,
+			Err(msg) => {
+				let e = RpcError::new(server::PARSE_ERROR, msg.to_string());
+				return vec![server::response(Json::Null, Err(e))];
+			}
+		};
+
+		let method = message["method"].as_str();
+		let params = &message["params"];
+
+		match (message.get("id"), method) {
+			(Some(id), Some(method)) => vec![server::response(id.clone(), self.call(method, params))],
+			(None, Some(method)) => self.notify(method, params),
+			// responses to requests from the server, which it never makes
+			_ => vec![],
+		}
+	}
+} // Server
+
+// reads the body of the next message, or None at the end of the input
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+	let mut length = None;
+
+	loop {
+		let mut header = String::new();
+		if input.read_line(&mut header)? == 0 {
+			return Ok(None);
+		}
+
+		let header = header.trim_end();
+		if header.is_empty() {
+			break;
+		}
+		if let Some((name, value)) = header.split_once(':') {
+			if name.eq_ignore_ascii_case("Content-Length") {
+				length = value.trim().parse::<usize>().ok();
+			}
+		}
+	}
+
+	let length = length.ok_or_else(|| io::Error::other("Expected a Content-Length header"))?;
+	let mut body = vec![0; length];
+	input.read_exact(&mut body)?;
+
+	Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+}
+
+// returns whether the client shut the server down before exiting
+pub fn serve<R: BufRead, W: Write>(
+	mut input: R,
+	mut output: W,
+	new_runner: &NewRunner,
+	format: Format,
+) -> Result<bool, String> {
+	let mut server = Server::new(new_runner, format);
+
+	while let Some(body) = read_message(&mut input).map_err(|msg| format!("Failed to read message: {}", msg))? {
+		for message in server.handle(&body) {
+			let body = message.to_string();
+			write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+				.and_then(|_| output.flush())
+				.map_err(|msg| format!("Failed to write message: {}", msg))?;
+		}
+
+		if server.exited {
+			break;
+		}
+	}
+
+	Ok(server.shutdown)
+}
+
+pub fn serve_stdio(new_runner: &NewRunner, format: Format) -> Result<bool, String> {
+	serve(io::stdin().lock(), io::stdout().lock(), new_runner, format)
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::lsp::*;
+
+	const SHEET: &str = "rent = 1200\nfood = 340 // per month\ntotal = (rent + food) * 12\n1 +* 2\nrent / 0\nconst vat = 0.2\nrent = 1300";
+
+	fn new_runner() -> Result<Runner, String> {
+		Ok(Runner::new())
+	}
+
+	fn doc() -> Document {
+		Document::new(SHEET.to_string(), Runner::new())
+	}
+
+	#[test]
+	fn analyze_documents() {
+		let doc = doc();
+		let format = Format::default();
+
+		assert_eq!(
+			doc.errors,
+			vec![
+				(Span { line: 4, column: 4, length: 1 }, "Unexpected token 'Star'".to_string()),
+				(Span { line: 5, column: 1, length: 8 }, "Cannot divide by zero".to_string()),
+			]
+		);
+
+		assert_eq!(doc.hover(3, 2, &format), Some("```\ntotal = 18480\n```".to_string()));
+		assert_eq!(doc.hover(3, 11, &format), Some("```\nrent = 1200\n```".to_string()));
+		assert_eq!(doc.hover(7, 2, &format), Some("```\nrent = 1300\n```".to_string()));
+		assert_eq!(doc.hover(3, 24, &format), Some("```\n= 18480\n```".to_string()));
+		assert_eq!(doc.hover(5, 6, &format), None);
+
+		let def = doc.definition("rent", 3).unwrap();
+		assert_eq!(def.span, Span { line: 1, column: 1, length: 4 });
+		assert_eq!(doc.definition("vat", 1).unwrap().span, Span { line: 6, column: 7, length: 3 });
+		assert!(doc.definition("pi", 1).is_none());
+
+		assert_eq!(
+			doc.complete(3, 13, &format),
+			json!([{ "label": "rent", "kind": KIND_VARIABLE, "detail": "1300" }])
+		);
+		assert!(doc.complete(1, 3, &format)
+			.as_array()
+			.unwrap()
+			.contains(&json!({ "label": "reset", "kind": KIND_KEYWORD })));
+	}
+
+	#[test]
+	fn handle_messages() {
+		let mut server = Server::new(&new_runner, Format::default());
+
+		let init = server.handle(r#"{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}"#);
+		assert_eq!(init[0]["result"]["capabilities"]["hoverProvider"], json!(true));
+
+		let open = json!({
+			"jsonrpc": "2.0",
+			"method": "textDocument/didOpen",
+			"params": { "textDocument": { "uri": "file:///a.tc", "text": "x = 2\n\"💰\" +* x" } },
+		});
+		let published = server.handle(&open.to_string());
+		assert_eq!(
+			published[0]["params"]["diagnostics"],
+			json!([{
+				"range": { "start": { "line": 1, "character": 6 }, "end": { "line": 1, "character": 7 } },
+				"severity": SEVERITY_ERROR,
+				"source": "tcalc",
+				"message": "Unexpected token 'Star'",
+			}])
+		);
+
+		let definition = json!({
+			"jsonrpc": "2.0",
+			"id": 2,
+			"method": "textDocument/definition",
+			"params": { "textDocument": { "uri": "file:///a.tc" }, "position": { "line": 1, "character": 8 } },
+		});
+		assert_eq!(
+			server.handle(&definition.to_string())[0]["result"],
+			json!({
+				"uri": "file:///a.tc",
+				"range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 1 } },
+			})
+		);
+
+		let hover = server.handle(r#"{"jsonrpc": "2.0", "id": 3, "method": "textDocument/hover", "params": {"textDocument": {"uri": "file:///b.tc"}, "position": {"line": 0, "character": 0}}}"#);
+		assert_eq!(hover[0]["error"]["code"], json!(server::INVALID_PARAMS));
+
+		assert_eq!(server.handle(r#"{"jsonrpc": "2.0", "id": 4, "method": "shutdown"}"#)[0]["result"], Json::Null);
+		assert!(server.handle(r#"{"jsonrpc": "2.0", "method": "exit"}"#).is_empty());
+		assert!(server.shutdown && server.exited);
+	}
+}
@@ -1,39 +1,85 @@
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{CompletionType, Config, Editor, EventHandler, KeyEvent};
 use std::env;
-use tcalc_rustyline::error::ReadlineError;
-use tcalc_rustyline::Editor;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
 
 #[macro_use]
 mod macros;
 
 mod ast;
 mod buffered_iterator;
+mod builtins;
+mod completion;
+mod datetime;
+mod explain;
+mod help;
+mod helper;
+mod highlighting;
+mod json;
+mod lsp;
+mod math;
+mod options;
 mod parsing;
+mod rpn;
 mod running;
 mod scanning;
+mod server;
+mod session;
+mod template;
+mod value;
 
 use crate::ast::*;
+use crate::helper::*;
+use crate::json::Outcome;
+use crate::options::{Options, Output, Parsed};
+use crate::rpn::*;
 use crate::running::*;
+use crate::scanning::SyntaxError;
+use crate::value::Format;
 
-fn print_usage() {
-	println!("Usage: {} [OPTION] EXPRESSIONS", env!("CARGO_PKG_NAME"));
+fn usage() -> String {
+	format!(
+		"Usage: {0} [OPTION]... [EXPRESSION]...\n  or:  {0} [OPTION]... SCRIPT [EXPRESSION]...\n  or:  {0} -t [OPTION]... [FILE]...\n  or:  {0} --serve [OPTION]...\n  or:  {0} --lsp [OPTION]...\n",
+		env!("CARGO_PKG_NAME")
+	)
+}
+
+fn print_stdin_note() {
+	println!("With no EXPRESSION, or when EXPRESSION is -, read expressions from standard");
+	println!("input, one per line. The REPL only starts when standard input is a terminal.");
+	println!("A SCRIPT is a file starting with a #! line, e.g. #!/usr/bin/env tcalc.");
+	println!();
+	println!("Values are printed to standard output, and errors to standard error. The exit");
+	println!("status is 1 if any input failed, 2 for invalid options, and 0 otherwise.");
 }
 
 fn print_opts() {
 	println!("Options:");
-	println!("    --help              print this help menu");
-	println!("    --version           print version information");
+	print!("{}", options::help());
+	println!();
+	println!("Options end at the first EXPRESSION, or at --, which lets an EXPRESSION");
+	println!("start with a letter after -, e.g. tcalc -- -pi.");
 }
 
 fn print_help() {
-	print_usage();
+	print!("{}", usage());
+	println!();
+	print_stdin_note();
 	println!();
 	print_opts();
 }
 
+// shown on stderr after a usage error
 fn print_try_help() {
-	print_usage();
-	println!();
-	println!(
+	eprint!("{}", usage());
+	eprintln!();
+	eprintln!(
 		"Try '{} --help' for more information.",
 		env!("CARGO_PKG_NAME")
 	);
@@ -43,30 +89,440 @@ fn print_version() {
 	println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
 }
 
-fn run_exprs<I>(inputs: I)
-where
-	I: Iterator<Item = String>,
-{
+fn vars_text(runner: &Runner, format: &Format) -> String {
+	let mut out = String::new();
+
+	for (i, scope) in runner.scopes().iter().enumerate() {
+		out.push_str(if i == 0 { "system:\n" } else { "user:\n" });
+
+		let mut names: Vec<&String> = scope.keys().collect();
+		names.sort();
+
+		for name in names {
+			let keyword = if i > 0 && runner.is_const(name) {
+				"const "
+			} else {
+				""
+			};
+			out.push_str(&format!("  {}{} = {}\n", keyword, name, format.value(&scope[name])));
+		}
+	}
+
+	out
+}
+
+fn funcs_text() -> String {
+	builtins::BUILTINS
+		.iter()
+		.map(|builtin| format!("  {}\n", builtin.signature()))
+		.collect()
+}
+
+// the top of the stack is shown last, nearest the prompt
+fn print_stack(stack: &Stack, format: &Format) {
+	let depth = stack.values().len();
+	for (i, value) in stack.values().iter().enumerate() {
+		println!("  {}: {}", depth - i, format.value(value));
+	}
+}
+
+fn session_path(path: &Option<String>) -> Result<PathBuf, String> {
+	match path {
+		Some(path) => Ok(session::expand_path(path)),
+		None => session::default_path().ok_or_else(|| "No default session file available".to_string()),
+	}
+}
+
+struct Startup {
+	init: Option<PathBuf>,
+	// from -D, run after the init script
+	defines: Vec<String>,
+}
+
+// sets a variable from NAME=VALUE, where VALUE is any expression
+fn define(runner: &mut Runner, def: &str) -> Result<(), String> {
+	let name = def.split_once('=').map_or(def, |(name, _)| name).trim();
+
+	let expr = match parsing::try_parse(def) {
+		Ok(Some(Ast::Expression(expr @ Expression::Assignment(_)))) => expr,
+		Ok(_) => return Err(format!("Expected NAME=VALUE, but found \"{}\"", def)),
+		Err(msg) => return Err(format!("Failed to define \"{}\": {}", name, msg)),
+	};
+
+	// compound assignments, like x+=1, also parse as assignments
+	if !matches!(expr, Expression::Assignment(ref a) if a.var.name == name) {
+		return Err(format!("Expected NAME=VALUE, but found \"{}\"", def));
+	}
+
+	runner
+		.evaluate(&expr)
+		.map(|_| ())
+		.map_err(|msg| format!("Failed to define \"{}\": {}", name, msg))
+}
+
+fn new_runner(startup: &Startup) -> Result<Runner, String> {
 	let mut runner = Runner::new();
 
-	for str in inputs {
-		match parsing::parse(&str) {
-			Some(Ast::Expression(expr)) => match runner.run_expression(&expr) {
-				Ok(v) => println!("{}", v),
-				Err(msg) => println!("{}", msg),
+	if let Some(ref path) = startup.init {
+		if path.exists() {
+			if let Err(msg) = session::load(&mut runner, path) {
+				eprintln!("{}", msg);
+			}
+		}
+	}
+
+	for def in &startup.defines {
+		define(&mut runner, def)?;
+	}
+
+	Ok(runner)
+}
+
+// the angle setting is kept by the runner, since it changes values
+#[derive(Clone, Copy)]
+struct Settings {
+	mode: Mode,
+	format: Format,
+}
+
+fn run_command(
+	runner: &mut Runner,
+	cmd: &Command,
+	startup: &Startup,
+	settings: &mut Settings,
+) -> Result<String, String> {
+	match cmd {
+		Command::Autosave(_) => Err("Autosave is only available in the REPL".to_string()),
+		Command::Clear => {
+			runner.clear();
+			Ok(String::new())
+		}
+		Command::Exit => Ok(String::new()),
+		Command::Explain(expr) => {
+			let (trace, result) = runner.traced(|runner| runner.evaluate(expr));
+			let text = explain::explain(expr, &trace, &settings.format);
+			match result {
+				Ok(_) => Ok(text),
+				Err(msg) => Err(format!("{}{}", text, msg)),
+			}
+		}
+		Command::Funcs => Ok(funcs_text()),
+		Command::Help(topic) => help::help(topic.as_deref()),
+		Command::Load(path) => session_path(path)
+			.and_then(|p| session::load(runner, &p))
+			.map(|_| String::new()),
+		Command::Reset => {
+			let angle = runner.angle();
+			*runner = new_runner(startup)?;
+			runner.set_angle(angle);
+			Ok(String::new())
+		}
+		Command::Save(path) => session_path(path)
+			.and_then(|p| session::save(runner, &p))
+			.map(|_| String::new()),
+		Command::Set(setting) => {
+			match *setting {
+				Setting::Angle(angle) => runner.set_angle(angle),
+				Setting::Mode(mode) => settings.mode = mode,
+				Setting::Notation(notation) => settings.format.notation = notation,
+				Setting::Precision(precision) => settings.format.precision = precision,
+				Setting::Radix(radix) => settings.format.radix = radix,
+			}
+			Ok(String::new())
+		}
+		Command::Vars => Ok(vars_text(runner, &settings.format)),
+	}
+}
+
+fn set_autosave(runner: &Runner, enable: bool) -> Result<(), String> {
+	let path = unwrap!(session::autosave_path(), {
+		return Err("No autosave file available".to_string());
+	});
+
+	if enable {
+		session::save(runner, &path)
+	} else if path.exists() {
+		fs::remove_file(&path)
+			.map_err(|msg| format!("Failed to remove '{}': {}", path.display(), msg))
+	} else {
+		Ok(())
+	}
+}
+
+struct Cli {
+	runner: Runner,
+	stack: Stack,
+	startup: Startup,
+	settings: Settings,
+	output: Output,
+	// stop at the first error, rather than running the remaining inputs
+	fail_fast: bool,
+	// only report errors from scripts
+	quiet: bool,
+	// print how each expression is reduced, before its value
+	explain: bool,
+	failed: bool,
+}
+
+impl Cli {
+	fn new(opts: &Options, startup: Startup, mut runner: Runner) -> Cli {
+		runner.set_angle(opts.angle);
+
+		Cli {
+			runner,
+			stack: Stack::new(),
+			startup,
+			settings: Settings {
+				mode: opts.mode,
+				format: opts.format,
 			},
-			Some(Ast::Statement(stmt)) => match runner.run_statement(&stmt) {
-				Ok(_) => {}
-				Err(msg) => println!("{}", msg),
+			output: opts.output,
+			fail_fast: opts.fail_fast,
+			quiet: opts.quiet,
+			explain: opts.explain,
+			failed: false,
+		}
+	}
+
+	fn stopped(&self) -> bool {
+		self.fail_fast && self.failed
+	}
+
+	fn report(&mut self, source: json::Source, result: Result<Outcome, SyntaxError>) {
+		self.failed |= result.is_err();
+
+		if self.quiet && result.is_ok() && matches!(source, json::Source::Script(..)) {
+			return;
+		}
+
+		let format = &self.settings.format;
+		if self.output == Output::Json {
+			println!("{}", json::record(&source, &result, &self.runner, format));
+			return;
+		}
+
+		match result {
+			Ok(outcome) => {
+				print!("{}", outcome.output);
+				if let (Some(v), true) = (&outcome.value, outcome.shows_value()) {
+					println!("{}", format.value(v));
+				}
+			}
+			Err(e) => match source {
+				json::Source::Script(path, line, _) => match e.position {
+					Some((line, column)) => eprintln!("{}:{}:{}: {}", path.display(), line, column, e.message),
+					None => eprintln!("{}:{}: {}", path.display(), line, e.message),
+				},
+				_ => eprintln!("{}", e),
 			},
-			_ => {}
-		} // match
-	} // for
-} // run_exprs
+		}
+	}
 
-fn repl() {
-	let mut runner = Runner::new();
-	let mut rl = Editor::<()>::new();
+	fn run_ast(&mut self, ast: Ast) -> Result<Outcome, String> {
+		match ast {
+			Ast::Command(cmd) => {
+				let output = run_command(&mut self.runner, &cmd, &self.startup, &mut self.settings)?;
+				Ok(Outcome::new("command").with_output(output))
+			}
+			Ast::Expression(expr) => {
+				let (explained, result) = if self.explain {
+					let (trace, result) = self.runner.traced(|runner| runner.run_numbered(&expr));
+					(explain::explain(&expr, &trace, &self.settings.format), result)
+				} else {
+					(String::new(), self.runner.run_numbered(&expr))
+				};
+
+				let (n, v) = result.map_err(|msg| format!("{}{}", explained, msg))?;
+				let outcome = Outcome::new("expression")
+					.with_value(Some(v))
+					.with_number(n)
+					.with_output(explained);
+				Ok(match expr {
+					Expression::Assignment(a) => outcome.with_name(a.var.name),
+					_ => outcome,
+				})
+			}
+			Ast::Statement(stmt) => {
+				self.runner.run_statement(&stmt)?;
+				Ok(match stmt {
+					Statement::Const(a) => Outcome::new("const").with_name(a.var.name),
+					Statement::DeleteVar(v) => Outcome::new("delete").with_name(v.name),
+					Statement::SetResult(n, _) => {
+						let value = self.runner.results().get(&n).cloned();
+						Outcome::new("set_result").with_value(value).with_number(n)
+					}
+				})
+			}
+		}
+	} // run_ast
+
+	// runs one complete input in the current mode
+	fn run_input(&mut self, input: &str) -> Result<Outcome, SyntaxError> {
+		let located = |msg| SyntaxError::new(msg, None);
+
+		match self.settings.mode {
+			Mode::Infix => match parsing::try_parse_located(input)? {
+				Some(ast) => self.run_ast(ast).map_err(located),
+				None => Ok(Outcome::new("empty")),
+			},
+			Mode::Rpn => match rpn::command(input) {
+				Some(cmd) => self.run_ast(Ast::Command(cmd)).map_err(located),
+				None => match self.stack.run_line(&mut self.runner, input) {
+					Ok(_) => Ok(Outcome::new("rpn").with_value(self.stack.top().cloned())),
+					Err(msg) => Err(located(msg)),
+				},
+			},
+		}
+	}
+
+	// scripts are always read as infix, even in RPN mode
+	fn run_script(&mut self, path: &Path) {
+		let text = match fs::read_to_string(path) {
+			Ok(text) => text,
+			Err(msg) => {
+				let msg = format!("Failed to read '{}': {}", path.display(), msg);
+				self.report(json::Source::File(path), Err(SyntaxError::new(msg, None)));
+				return;
+			}
+		};
+
+		for (line, statement, ast) in parsing::parse_script_sourced(&text) {
+			let result = ast.and_then(|ast| self.run_ast(ast).map_err(|msg| SyntaxError::new(msg, None)));
+			self.report(json::Source::Script(path, line, statement), result);
+
+			if self.stopped() {
+				return;
+			}
+		}
+	} // run_script
+
+	fn run_exprs<I>(&mut self, inputs: I)
+	where
+		I: Iterator<Item = String>,
+	{
+		let mut pending = String::new();
+
+		// a trailing incomplete input is still run, so its error is shown
+		for str in inputs.map(Some).chain(iter::once(None)) {
+			if self.stopped() {
+				return;
+			}
+
+			let str = match str {
+				Some(str) => match push_line(&mut pending, &str, self.settings.mode == Mode::Infix) {
+					Some(input) => input,
+					None => continue,
+				},
+				None if pending.is_empty() => break,
+				None => std::mem::take(&mut pending),
+			};
+
+			let result = self.run_input(&str);
+			self.report(json::Source::Input(&str), result);
+		} // for
+	} // run_exprs
+} // Cli
+
+// returns whether every block ran
+fn run_templates(mut runner: Runner, paths: &[String], opts: &Options) -> bool {
+	runner.set_angle(opts.angle);
+	let mut ok = true;
+
+	let stdin = ["-".to_string()];
+	let paths = if paths.is_empty() { &stdin[..] } else { paths };
+
+	for path in paths {
+		let (name, text) = if path == "-" {
+			("stdin", io::read_to_string(io::stdin()))
+		} else {
+			(path.as_str(), fs::read_to_string(path))
+		};
+
+		let text = match text {
+			Ok(text) => text,
+			Err(msg) => {
+				eprintln!("Failed to read '{}': {}", name, msg);
+				ok = false;
+				continue;
+			}
+		};
+
+		let (out, errors) = template::render(&mut runner, &text, &opts.format);
+		print!("{}", out);
+		for (line, msg) in &errors {
+			eprintln!("{}:{}: {}", name, line, msg);
+		}
+		ok &= errors.is_empty();
+	}
+
+	ok
+} // run_templates
+
+// only the first two bytes are read, so large files given as expressions by
+// mistake are not loaded
+fn is_script(arg: &str) -> bool {
+	let path = Path::new(arg);
+	let mut shebang = [0u8; 2];
+	path.is_file()
+		&& fs::File::open(path).is_ok_and(|mut file| file.read_exact(&mut shebang).is_ok())
+		&& &shebang == b"#!"
+}
+
+// adds a line of input to the pending lines, returning them once they are
+// complete; only input which ends early continues, so an error is shown at once
+fn push_line(pending: &mut String, line: &str, continues: bool) -> Option<String> {
+	if pending.is_empty() && line.trim().is_empty() {
+		return None;
+	}
+
+	if !pending.is_empty() {
+		pending.push('\n');
+	}
+	pending.push_str(line);
+
+	if continues && parsing::is_incomplete(pending) {
+		None
+	} else {
+		Some(std::mem::take(pending))
+	}
+}
+
+// lines are read as they are needed, so results appear while input is still
+// being written
+fn stdin_lines() -> impl Iterator<Item = String> {
+	io::stdin().lines().map_while(Result::ok)
+}
+
+// history and completion are set as they were with the tcalc-rustyline fork,
+// rather than relying on rustyline's defaults
+fn editor_config() -> rustyline::Result<Config> {
+	Ok(Config::builder()
+		.max_history_size(100)?
+		.history_ignore_dups(true)?
+		.history_ignore_space(false)
+		.completion_type(CompletionType::Circular)
+		.build())
+}
+
+fn repl(startup: &Startup, opts: &Options, mut runner: Runner) {
+	runner.set_angle(opts.angle);
+	let mut settings = Settings {
+		mode: opts.mode,
+		format: opts.format,
+	};
+	let mut stack = Stack::new();
+	let mut rl = match editor_config().and_then(Editor::<ReplHelper, DefaultHistory>::with_config) {
+		Ok(rl) => rl,
+		Err(msg) => {
+			println!("Failed to start the REPL: {}", msg);
+			return;
+		}
+	};
+	rl.set_helper(Some(ReplHelper::new(use_color())));
+	rl.bind_sequence(
+		KeyEvent::ctrl('c'),
+		EventHandler::Conditional(Box::new(CancelHandler)),
+	);
 
 	let history_path = match dirs::cache_dir() {
 		Some(mut hist_dir) => {
@@ -80,30 +536,99 @@ fn repl() {
 		let _ = rl.load_history(&path);
 	}
 
-	loop {
-		match rl.readline("> ") {
-			Ok(line) => {
-				rl.add_history_entry(line.as_str());
-				match parsing::parse(&line) {
-					Some(Ast::Command(Command::Exit)) => break,
-					Some(Ast::Expression(expr)) => match runner.run_expression(&expr) {
-						Ok(v) => println!("  {}", v),
-						Err(msg) => println!("{}", msg),
-					},
-					Some(Ast::Statement(stmt)) => match runner.run_statement(&stmt) {
-						Ok(_) => {}
-						Err(msg) => println!("{}", msg),
-					},
-					None => {}
-				} // match
+	// autosave is enabled for as long as the autosave file exists
+	let autosave_path = session::autosave_path();
+	let mut autosave = matches!(autosave_path, Some(ref path) if path.exists());
+	if let (true, Some(ref path)) = (autosave, &autosave_path) {
+		if let Err(msg) = session::load(&mut runner, path) {
+			println!("{}", msg);
+		}
+	}
+
+	let mut pending = String::new();
+
+	'repl: loop {
+		if let Some(helper) = rl.helper_mut() {
+			helper.update_session(&runner, &pending);
+			helper.update_stack(settings.mode, &stack);
+		}
+
+		// incomplete input is continued on the following lines
+		let prompt = if pending.is_empty() { "> " } else { "... " };
+		match rl.readline(prompt) {
+			Ok(input) => {
+				let _ = rl.add_history_entry(input.as_str());
+
+				// pasted input may hold many lines
+				for line in input.lines() {
+					if !pending.is_empty() {
+						pending.push('\n');
+					}
+					pending.push_str(line);
+
+					// RPN lines often end with an operator, so they are never continued
+					if settings.mode == Mode::Infix && parsing::is_incomplete(&pending) {
+						continue;
+					}
+
+					let line = std::mem::take(&mut pending);
+					let ast = match settings.mode {
+						Mode::Infix => parsing::parse(&line),
+						Mode::Rpn => match rpn::command(&line) {
+							Some(cmd) => Some(Ast::Command(cmd)),
+							None => {
+								match stack.run_line(&mut runner, &line) {
+									Ok(_) => print_stack(&stack, &settings.format),
+									Err(msg) => println!("{}", msg),
+								}
+								continue;
+							}
+						},
+					};
+
+					match ast {
+						Some(Ast::Command(Command::Exit)) => break 'repl,
+						Some(Ast::Command(Command::Autosave(None))) => {
+							println!("  autosave is {}", if autosave { "on" } else { "off" });
+						}
+						Some(Ast::Command(Command::Autosave(Some(enable)))) => {
+							match set_autosave(&runner, enable) {
+								Ok(_) => autosave = enable,
+								Err(msg) => println!("{}", msg),
+							}
+						}
+						Some(Ast::Command(cmd)) => {
+							match run_command(&mut runner, &cmd, startup, &mut settings) {
+								Ok(text) => print!("{}", text),
+								Err(msg) => println!("{}", msg),
+							}
+						}
+						Some(Ast::Expression(expr)) => match runner.run_numbered(&expr) {
+							Ok((n, v)) => println!("[{}] {}", n, settings.format.value(&v)),
+							Err(msg) => println!("{}", msg),
+						},
+						Some(Ast::Statement(stmt)) => match runner.run_statement(&stmt) {
+							Ok(_) => {}
+							Err(msg) => println!("{}", msg),
+						},
+						None => {}
+					} // match
+				} // for
 			}
-			Err(ReadlineError::Cancelled) => {}
+			// ctrl-c on an empty continuation line discards the unfinished input
+			Err(ReadlineError::Interrupted) if !pending.is_empty() => pending.clear(),
 			Err(ReadlineError::Interrupted) => break,
 			Err(ReadlineError::Eof) => break,
 			Err(msg) => println!("error: {}", msg),
 		} // match
 	} // loop
 
+	if autosave {
+		if let Err(msg) = set_autosave(&runner, true) {
+			println!("{}", msg);
+		}
+	}
+
 	if let Some(ref path) = history_path {
 		if let Err(msg) = rl.save_history(&path) {
 			println!("Failed to save history: '{}'", msg);
@@ -111,39 +636,199 @@ fn repl() {
 	}
 } // repl
 
-fn main() {
-	let mut args = env::args();
+fn runner_factory(startup: Startup, opts: &Options) -> Arc<server::NewRunner> {
+	let angle = opts.angle;
+
+	Arc::new(move || {
+		let mut runner = new_runner(&startup)?;
+		runner.set_angle(angle);
+		Ok(runner)
+	})
+}
+
+// each connection, and each reset, starts a new session
+fn serve(startup: Startup, opts: &Options) -> ExitCode {
+	let new_runner = runner_factory(startup, opts);
+
+	#[cfg(unix)]
+	let result = match opts.socket {
+		Some(ref path) => server::serve_socket(path, new_runner, opts.format),
+		None => server::serve_stdio(new_runner.as_ref(), opts.format),
+	};
+	#[cfg(not(unix))]
+	let result = server::serve_stdio(new_runner.as_ref(), opts.format);
 
-	// start repl if there are no arguments
-	if args.len() < 2 {
-		repl();
-		return;
+	match result {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(msg) => {
+			eprintln!("{}", msg);
+			ExitCode::FAILURE
+		}
 	}
+} // serve
 
-	// check for arguments
-	let mut peekable = args.by_ref().skip(1).peekable();
-	while let Some(arg) = peekable.peek() {
-		match arg.as_str() {
-			"--help" => {
-				print_help();
-				return;
-			}
-			"--version" => {
-				print_version();
-				return;
-			}
-			str => {
-				if str.starts_with("--") {
-					println!("Unrecognized option '{}'", str);
-					println!();
-					print_try_help();
-					return;
-				}
+// the protocol asks for a shutdown before exit for a successful exit code
+fn serve_lsp(startup: Startup, opts: &Options) -> ExitCode {
+	let new_runner = runner_factory(startup, opts);
+
+	match lsp::serve_stdio(new_runner.as_ref(), opts.format) {
+		Ok(true) => ExitCode::SUCCESS,
+		Ok(false) => ExitCode::FAILURE,
+		Err(msg) => {
+			eprintln!("{}", msg);
+			ExitCode::FAILURE
+		}
+	}
+}
+
+fn main() -> ExitCode {
+	let opts = match options::parse(env::args().skip(1)) {
+		Ok(Parsed::Help) => {
+			print_help();
+			return ExitCode::SUCCESS;
+		}
+		Ok(Parsed::Run(opts)) => opts,
+		Ok(Parsed::Version) => {
+			print_version();
+			return ExitCode::SUCCESS;
+		}
+		Err(msg) => {
+			eprintln!("{}", msg);
+			eprintln!();
+			print_try_help();
+			return ExitCode::from(2);
+		}
+	};
+
+	if opts.template && !opts.scripts.is_empty() {
+		eprintln!("Option '--template' cannot be used with '--file'");
+		eprintln!();
+		print_try_help();
+		return ExitCode::from(2);
+	}
+
+	if opts.serve && opts.lsp {
+		eprintln!("Option '--serve' cannot be used with '--lsp'");
+		eprintln!();
+		print_try_help();
+		return ExitCode::from(2);
+	}
+
+	let server = if opts.lsp { "--lsp" } else { "--serve" };
+	if (opts.serve || opts.lsp) && (opts.template || !opts.scripts.is_empty() || !opts.inputs.is_empty()) {
+		eprintln!("Option '{}' cannot be used with templates, scripts or expressions", server);
+		eprintln!();
+		print_try_help();
+		return ExitCode::from(2);
+	}
+
+	let init = match opts.init_file {
+		_ if opts.no_init => None,
+		Some(ref path) if !path.is_file() => {
+			eprintln!("Init script '{}' does not exist", path.display());
+			return ExitCode::from(2);
+		}
+		Some(ref path) => Some(path.clone()),
+		None => session::init_path(),
+	};
+	let startup = Startup {
+		init,
+		defines: opts.defines.clone(),
+	};
+	let runner = match new_runner(&startup) {
+		Ok(runner) => runner,
+		Err(msg) => {
+			eprintln!("{}", msg);
+			return ExitCode::from(2);
+		}
+	};
+
+	// the runner only checked the startup, since each session makes its own
+	if opts.lsp {
+		return serve_lsp(startup, &opts);
+	}
+	if opts.serve {
+		return serve(startup, &opts);
+	}
+
+	if opts.template {
+		return if run_templates(runner, &opts.inputs, &opts) {
+			ExitCode::SUCCESS
+		} else {
+			ExitCode::FAILURE
+		};
+	}
+
+	let mut scripts = opts.scripts.clone();
+	let mut args = opts.inputs.iter().cloned().peekable();
+
+	// a file starting with #! is run as a script, as the kernel does for
+	// executable scripts naming tcalc
+	if scripts.is_empty() {
+		if let Some(path) = args.next_if(|arg| is_script(arg)) {
+			scripts.push(PathBuf::from(path));
+		}
+	}
+
+	// start repl if there are no expressions, unless input is piped in
+	let no_inputs = args.peek().is_none() && scripts.is_empty();
+	if no_inputs && io::stdin().is_terminal() {
+		repl(&startup, &opts, runner);
+		return ExitCode::SUCCESS;
+	}
+
+	let mut cli = Cli::new(&opts, startup, runner);
+	for path in scripts {
+		if !cli.stopped() {
+			cli.run_script(&path);
+		}
+	}
+
+	// evaluate remaining inputs, where - stands for each line of stdin; only
+	// lines of stdin continue on the next, so each argument stands alone
+	if no_inputs {
+		cli.run_exprs(stdin_lines());
+	} else {
+		for arg in args {
+			if cli.stopped() {
 				break;
+			} else if arg == "-" {
+				cli.run_exprs(stdin_lines());
+			} else {
+				cli.run_exprs(iter::once(arg));
 			}
 		}
 	}
 
-	// evaluate remaining inputs
-	run_exprs(peekable);
+	if cli.failed {
+		ExitCode::FAILURE
+	} else {
+		ExitCode::SUCCESS
+	}
 } // main
+
+#[cfg(test)]
+mod tests {
+	use crate::*;
+
+	// groups lines of piped input into the inputs which are run
+	fn inputs(lines: &[&str]) -> Vec<String> {
+		let mut pending = String::new();
+		let mut inputs: Vec<String> = lines
+			.iter()
+			.filter_map(|line| push_line(&mut pending, line, true))
+			.collect();
+		if !pending.is_empty() {
+			inputs.push(pending);
+		}
+
+		inputs
+	}
+
+	#[test]
+	fn stdin_continued_lines() {
+		assert_eq!(inputs(&["a = (1 +", "", "2)", "a"]), ["a = (1 +\n\n2)", "a"]);
+		assert_eq!(inputs(&["abs(1 2", "x = 3", "x"]), ["abs(1 2", "x = 3", "x"]);
+		assert_eq!(inputs(&["1 +", "2 *"]), ["1 +\n2 *"]);
+	}
+}
@@ -0,0 +1,403 @@
+// integer inputs are 64 bits, with products computed in 128 bits to stay exact
+
+use std::f64::consts::PI;
+
+// every integer below this is exact as an f64
+pub const MAX_EXACT_INT: i64 = 1 << 53;
+
+pub fn gcd(a: i64, b: i64) -> i64 {
+	let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+	while b != 0 {
+		(a, b) = (b, a % b);
+	}
+
+	a as i64
+}
+
+pub fn lcm(a: i64, b: i64) -> Option<i64> {
+	if a == 0 || b == 0 {
+		return Some(0);
+	}
+
+	let l = (a / gcd(a, b)) as i128 * b as i128;
+	i64::try_from(l.abs()).ok()
+}
+
+// returns (g, x, y) such that a*x + b*y = g = gcd(a, b)
+pub fn egcd(a: i64, b: i64) -> (i64, i64, i64) {
+	let (mut old_r, mut r) = (a as i128, b as i128);
+	let (mut old_s, mut s) = (1i128, 0i128);
+	let (mut old_t, mut t) = (0i128, 1i128);
+
+	while r != 0 {
+		let q = old_r / r;
+		(old_r, r) = (r, old_r - q * r);
+		(old_s, s) = (s, old_s - q * s);
+		(old_t, t) = (t, old_t - q * t);
+	}
+
+	if old_r < 0 {
+		(old_r, old_s, old_t) = (-old_r, -old_s, -old_t);
+	}
+
+	(old_r as i64, old_s as i64, old_t as i64)
+}
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+	((a as u128 * b as u128) % m as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+	let mut result = 1 % m;
+	base %= m;
+
+	while exp > 0 {
+		if exp & 1 == 1 {
+			result = mul_mod(result, base, m);
+		}
+		base = mul_mod(base, base, m);
+		exp >>= 1;
+	}
+
+	result
+}
+
+// negative exponents use the modular inverse of b, if it exists
+pub fn modpow(b: i64, e: i64, m: i64) -> Option<i64> {
+	let m = m.unsigned_abs();
+	let mut base = b.rem_euclid(m as i64) as u64;
+
+	if e < 0 {
+		base = modinv(base as i64, m as i64)? as u64;
+	}
+
+	Some(pow_mod(base, e.unsigned_abs(), m) as i64)
+}
+
+pub fn modinv(a: i64, m: i64) -> Option<i64> {
+	let m = m.abs();
+	let (g, x, _) = egcd(a.rem_euclid(m), m);
+
+	if g != 1 {
+		None
+	} else {
+		Some(x.rem_euclid(m))
+	}
+}
+
+pub fn is_prime(n: i64) -> bool {
+	if n < 2 {
+		return false;
+	}
+
+	let n = n as u64;
+	for p in [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37] {
+		if n.is_multiple_of(p) {
+			return n == p;
+		}
+	}
+
+	// deterministic Miller-Rabin; these bases are sufficient for all n < 2^64
+	let mut d = n - 1;
+	let mut s = 0;
+	while d.is_multiple_of(2) {
+		d /= 2;
+		s += 1;
+	}
+
+	'bases: for a in [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37] {
+		let mut x = pow_mod(a, d, n);
+		if x == 1 || x == n - 1 {
+			continue;
+		}
+
+		for _ in 1..s {
+			x = mul_mod(x, x, n);
+			if x == n - 1 {
+				continue 'bases;
+			}
+		}
+
+		return false;
+	}
+
+	true
+}
+
+pub fn next_prime(n: i64) -> Option<i64> {
+	if n < 2 {
+		return Some(2);
+	}
+
+	let mut candidate = n.checked_add(1)?;
+	while !is_prime(candidate) {
+		candidate = candidate.checked_add(1)?;
+	}
+
+	Some(candidate)
+}
+
+// Pollard rho, with Floyd cycle detection; n must be composite and odd
+fn find_divisor(n: u64) -> u64 {
+	for c in 1.. {
+		let f = |x: u64| (mul_mod(x, x, n) + c) % n;
+		let (mut x, mut y, mut d) = (2, 2, 1);
+
+		while d == 1 {
+			x = f(x);
+			y = f(f(y));
+			d = gcd(x.abs_diff(y) as i64, n as i64) as u64;
+		}
+
+		if d != n {
+			return d;
+		}
+	}
+
+	unreachable!()
+}
+
+// factors are ascending, with repetition
+pub fn factor(n: i64) -> Vec<i64> {
+	let mut factors = vec![];
+	let mut n = n as u64;
+
+	for p in [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37] {
+		while n.is_multiple_of(p) {
+			factors.push(p as i64);
+			n /= p;
+		}
+	}
+
+	let mut pending = vec![n];
+	while let Some(m) = pending.pop() {
+		if m == 1 {
+			continue;
+		}
+
+		if is_prime(m as i64) {
+			factors.push(m as i64);
+		} else {
+			let d = find_divisor(m);
+			pending.push(d);
+			pending.push(m / d);
+		}
+	}
+
+	factors.sort_unstable();
+	factors
+}
+
+pub fn totient(n: i64) -> i64 {
+	let mut factors = factor(n);
+	factors.dedup();
+
+	factors
+		.into_iter()
+		.fold(n, |result, p| result / p * (p - 1))
+}
+
+// Lanczos approximation coefficients for g = 7, n = 9
+const LANCZOS_G: f64 = 7f64;
+const LANCZOS_COEF: [f64; 9] = [
+	0.999_999_999_999_809_9,
+	676.520_368_121_885_1,
+	-1_259.139_216_722_402_8,
+	771.323_428_777_653_1,
+	-176.615_029_162_140_6,
+	12.507_343_278_686_905,
+	-0.138_571_095_265_720_12,
+	9.984_369_578_019_572e-6,
+	1.505_632_735_149_311_6e-7,
+];
+
+// gamma(x) is larger than f64::MAX above this
+const GAMMA_MAX: f64 = 171.624_376_956_302_7;
+
+// overflows to infinity for n > 170
+pub fn factorial(n: u64) -> f64 {
+	if n > 170 {
+		return f64::INFINITY;
+	}
+
+	(2..=n).fold(1f64, |result, i| result * i as f64)
+}
+
+pub fn choose(n: u64, k: u64) -> f64 {
+	if k > n {
+		return 0f64;
+	}
+
+	// each partial result is itself a binomial coefficient, so the division is exact
+	let k = k.min(n - k);
+	let mut result = 1u128;
+	for i in 1..=k {
+		result = match result.checked_mul((n - k + i) as u128) {
+			Some(r) => r / i as u128,
+			None => {
+				let ln = lgamma((n + 1) as f64) - lgamma((k + 1) as f64) - lgamma((n - k + 1) as f64);
+				return ln.exp().round();
+			}
+		};
+	}
+
+	result as f64
+}
+
+pub fn permutations(n: u64, k: u64) -> f64 {
+	if k > n {
+		return 0f64;
+	}
+
+	let mut result = 1f64;
+	for i in (n - k + 1)..=n {
+		result *= i as f64;
+		if result.is_infinite() {
+			break;
+		}
+	}
+
+	result
+}
+
+pub fn gamma(x: f64) -> f64 {
+	if x.fract() == 0f64 && x > 0f64 && x <= 171f64 {
+		return factorial(x as u64 - 1);
+	}
+
+	if x > GAMMA_MAX {
+		// like factorial, overflows to infinity instead of NaN from inf * 0
+		return f64::INFINITY;
+	}
+
+	if x < 0.5 {
+		// reflection formula
+		return PI / ((PI * x).sin() * gamma(1f64 - x));
+	}
+
+	// t**(x - 0.5) is split in two, as it overflows before gamma(x) does
+	let (t, sum) = lanczos(x);
+	let half = t.powf((x - 0.5) / 2f64);
+	(2f64 * PI).sqrt() * half * (-t).exp() * half * sum
+}
+
+// ln(|gamma(x)|), which does not overflow for large x
+pub fn lgamma(x: f64) -> f64 {
+	if x < 0.5 {
+		return (PI / (PI * x).sin().abs()).ln() - lgamma(1f64 - x);
+	}
+
+	let (t, sum) = lanczos(x);
+	(2f64 * PI).sqrt().ln() + (x - 0.5) * t.ln() - t + sum.ln()
+}
+
+fn lanczos(x: f64) -> (f64, f64) {
+	let x = x - 1f64;
+	let t = x + LANCZOS_G + 0.5;
+	let sum = LANCZOS_COEF
+		.iter()
+		.enumerate()
+		.skip(1)
+		.fold(LANCZOS_COEF[0], |sum, (i, c)| sum + c / (x + i as f64));
+
+	(t, sum)
+}
+
+pub fn beta(a: f64, b: f64) -> f64 {
+	if a > 0f64 && b > 0f64 {
+		(lgamma(a) + lgamma(b) - lgamma(a + b)).exp()
+	} else {
+		gamma(a) * gamma(b) / gamma(a + b)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::math::*;
+
+	#[test]
+	fn test_gcd_lcm() {
+		assert_eq!(gcd(12, 18), 6);
+		assert_eq!(gcd(-12, 18), 6);
+		assert_eq!(gcd(0, 5), 5);
+		assert_eq!(lcm(4, 6), Some(12));
+		assert_eq!(lcm(0, 6), Some(0));
+		assert_eq!(lcm(MAX_EXACT_INT, 3), Some(27_021_597_764_222_976));
+		assert_eq!(lcm(i64::MAX, i64::MAX - 1), None);
+	}
+
+	#[test]
+	fn test_egcd() {
+		assert_eq!(egcd(240, 46), (2, -9, 47));
+		assert_eq!(egcd(0, -5), (5, 0, -1));
+	}
+
+	#[test]
+	fn test_modpow_modinv() {
+		assert_eq!(modpow(4, 13, 497), Some(445));
+		assert_eq!(modpow(-2, 3, 5), Some(2));
+		assert_eq!(modpow(3, -1, 7), Some(5));
+		assert_eq!(modpow(2, -1, 4), None);
+		assert_eq!(modinv(3, 11), Some(4));
+		assert_eq!(modinv(6, 9), None);
+	}
+
+	#[test]
+	fn test_primes() {
+		assert!(!is_prime(1));
+		assert!(is_prime(2));
+		assert!(is_prime(97));
+		assert!(!is_prime(561));
+		assert!(is_prime(9_007_199_254_740_881));
+		assert!(is_prime(i64::MAX - 24));
+		assert_eq!(next_prime(13), Some(17));
+		assert_eq!(next_prime(-4), Some(2));
+		assert_eq!(next_prime(i64::MAX - 24), None);
+	}
+
+	#[test]
+	fn test_factor() {
+		assert_eq!(factor(1), Vec::<i64>::new());
+		assert_eq!(factor(360), vec![2, 2, 2, 3, 3, 5]);
+		assert_eq!(factor(600_851_475_143), vec![71, 839, 1471, 6857]);
+		assert_eq!(
+			factor(9_007_199_254_740_879),
+			vec![3, 3, 43, 642_739, 36_211_303]
+		);
+		assert_eq!(totient(36), 12);
+		assert_eq!(totient(1), 1);
+	}
+
+	fn assert_close(actual: f64, expected: f64) {
+		let err = ((actual - expected) / expected).abs();
+		assert!(err < 1e-12, "expected {} but found {}", expected, actual);
+	}
+
+	#[test]
+	fn test_combinatorics() {
+		assert_eq!(factorial(0), 1f64);
+		assert_eq!(factorial(10), 3_628_800f64);
+		assert_eq!(factorial(171), f64::INFINITY);
+		assert_eq!(choose(5, 2), 10f64);
+		assert_eq!(choose(2, 5), 0f64);
+		assert_eq!(choose(60, 30), 118_264_581_564_861_424f64);
+		assert_close(choose(1000, 500), 2.702_882_409_454_365_5e299);
+		assert_eq!(permutations(5, 2), 20f64);
+		assert_eq!(permutations(5, 0), 1f64);
+	}
+
+	#[test]
+	fn test_gamma() {
+		assert_eq!(gamma(5f64), 24f64);
+		assert_close(gamma(0.5), PI.sqrt());
+		assert_close(gamma(-1.5), 4f64 * PI.sqrt() / 3f64);
+		assert_close(gamma(171.5), 9.483_367_566_824_801e307);
+		assert_eq!(gamma(171.7), f64::INFINITY);
+		assert_eq!(gamma(172f64), f64::INFINITY);
+		assert_eq!(gamma(1000f64), f64::INFINITY);
+		assert_close(lgamma(100f64), 359.134_205_369_575_4);
+		assert_close(lgamma(-0.5), (2f64 * PI.sqrt()).ln());
+		assert_close(beta(2f64, 3f64), 1f64 / 12f64);
+		assert_close(beta(-0.5, 2f64), -4f64);
+	}
+}
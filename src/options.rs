@@ -0,0 +1,474 @@
+// options come before any expressions, and -- ends them
+
+use std::path::PathBuf;
+
+use crate::ast::*;
+use crate::value::Format;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+	Json,
+	Text,
+}
+
+pub struct Opt {
+	pub short: Option<char>,
+	pub long: &'static str,
+	// the name of the value in help, if the option takes one
+	pub value: Option<&'static str>,
+	pub description: &'static str,
+}
+
+pub static OPTS: &[Opt] = &[
+	Opt {
+		short: Some('a'),
+		long: "angle",
+		value: Some("UNIT"),
+		description: "read and return angles in deg or rad (default)",
+	},
+	Opt {
+		short: Some('D'),
+		long: "define",
+		value: Some("NAME=VALUE"),
+		description: "set variable NAME to the value of expression VALUE",
+	},
+	Opt {
+		short: None,
+		long: "explain",
+		value: None,
+		description: "print how each expression is grouped and reduced, before its value",
+	},
+	Opt {
+		short: None,
+		long: "fail-fast",
+		value: None,
+		description: "stop at the first input which fails",
+	},
+	Opt {
+		short: Some('f'),
+		long: "file",
+		value: Some("FILE"),
+		description: "run the statements in FILE, then any expressions",
+	},
+	Opt {
+		short: Some('h'),
+		long: "help",
+		value: None,
+		description: "print this help menu",
+	},
+	Opt {
+		short: Some('i'),
+		long: "init",
+		value: Some("FILE"),
+		description: "run FILE instead of the init script",
+	},
+	Opt {
+		short: None,
+		long: "json",
+		value: None,
+		description: "same as --output json",
+	},
+	Opt {
+		short: None,
+		long: "keep-going",
+		value: None,
+		description: "run every input, even after one fails (default)",
+	},
+	Opt {
+		short: None,
+		long: "lsp",
+		value: None,
+		description: "run a language server for .tc files on standard input and output",
+	},
+	Opt {
+		short: Some('m'),
+		long: "mode",
+		value: Some("MODE"),
+		description: "read input as infix (default) or rpn",
+	},
+	Opt {
+		short: Some('n'),
+		long: "number",
+		value: Some("MODE"),
+		description: "print numbers as auto (default), fixed or sci",
+	},
+	Opt {
+		short: None,
+		long: "no-init",
+		value: None,
+		description: "skip running the init script",
+	},
+	Opt {
+		short: Some('o'),
+		long: "output",
+		value: Some("FORMAT"),
+		description: "report each input as text (default) or json",
+	},
+	Opt {
+		short: Some('p'),
+		long: "precision",
+		value: Some("N"),
+		description: "print numbers with up to N digits after the decimal point",
+	},
+	Opt {
+		short: Some('q'),
+		long: "quiet",
+		value: None,
+		description: "print only errors for script statements",
+	},
+	Opt {
+		short: Some('r'),
+		long: "radix",
+		value: Some("N"),
+		description: "print integers in base 2, 8, 10 (default) or 16",
+	},
+	Opt {
+		short: None,
+		long: "rpn",
+		value: None,
+		description: "same as --mode rpn",
+	},
+	Opt {
+		short: None,
+		long: "serve",
+		value: None,
+		description: "answer JSON-RPC requests, one per line, on standard input",
+	},
+	#[cfg(unix)]
+	Opt {
+		short: None,
+		long: "socket",
+		value: Some("PATH"),
+		description: "same as --serve, but listen on the Unix socket PATH",
+	},
+	Opt {
+		short: Some('t'),
+		long: "template",
+		value: None,
+		description: "print each FILE, or standard input, with each {{ expr }} replaced by its value",
+	},
+	Opt {
+		short: Some('V'),
+		long: "version",
+		value: None,
+		description: "print version information",
+	},
+];
+
+pub fn help() -> String {
+	let usages: Vec<String> = OPTS
+		.iter()
+		.map(|opt| {
+			let short = match opt.short {
+				Some(c) => format!("-{}, ", c),
+				None => "    ".to_string(),
+			};
+			let value = opt.value.map(|v| format!(" {}", v)).unwrap_or_default();
+			format!("{}--{}{}", short, opt.long, value)
+		})
+		.collect();
+	let width = usages.iter().map(|u| u.len()).max().unwrap_or(0);
+
+	let mut out = String::new();
+	for (usage, opt) in usages.iter().zip(OPTS) {
+		out.push_str(&format!("  {:width$}  {}\n", usage, opt.description, width = width));
+	}
+
+	out
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+	pub angle: Angle,
+	pub defines: Vec<String>,
+	pub explain: bool,
+	pub fail_fast: bool,
+	pub format: Format,
+	pub init_file: Option<PathBuf>,
+	pub lsp: bool,
+	pub mode: Mode,
+	pub no_init: bool,
+	pub output: Output,
+	pub quiet: bool,
+	pub scripts: Vec<PathBuf>,
+	pub serve: bool,
+	#[cfg(unix)]
+	pub socket: Option<PathBuf>,
+	pub template: bool,
+	pub inputs: Vec<String>,
+}
+
+impl Default for Options {
+	fn default() -> Options {
+		Options {
+			angle: Angle::Radians,
+			defines: vec![],
+			explain: false,
+			fail_fast: false,
+			format: Format::default(),
+			init_file: None,
+			lsp: false,
+			mode: Mode::Infix,
+			no_init: false,
+			output: Output::Text,
+			quiet: false,
+			scripts: vec![],
+			serve: false,
+			#[cfg(unix)]
+			socket: None,
+			template: false,
+			inputs: vec![],
+		}
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Parsed {
+	Help,
+	Run(Options),
+	Version,
+}
+
+// an option is - or -- followed by a letter
+fn is_option(arg: &str) -> bool {
+	let name = arg.strip_prefix("--").or_else(|| arg.strip_prefix('-'));
+	name.and_then(|name| name.chars().next()).is_some_and(|c| c.is_alphabetic())
+}
+
+// an option which fails may have been meant as an expression, like -pi
+const EXPRESSION_HINT: &str = "put -- before expressions starting with -";
+
+fn expected(flag: &str, values: &str) -> String {
+	format!("Option '{}' expects {}", flag, values)
+}
+
+// sets the option named long from the flag as it was given, e.g. -p or --precision
+fn set_option(opts: &mut Options, flag: &str, long: &str, value: Option<String>) -> Result<(), String> {
+	let value = value.unwrap_or_default();
+
+	match long {
+		"angle" => {
+			opts.angle = Angle::from_name(&value).ok_or_else(|| expected(flag, "deg or rad"))?;
+		}
+		"define" if value.contains('=') => opts.defines.push(value),
+		"define" => return Err(expected(flag, "NAME=VALUE")),
+		"explain" => opts.explain = true,
+		"fail-fast" => opts.fail_fast = true,
+		"file" => opts.scripts.push(PathBuf::from(value)),
+		"init" => opts.init_file = Some(PathBuf::from(value)),
+		"json" => opts.output = Output::Json,
+		"keep-going" => opts.fail_fast = false,
+		"lsp" => opts.lsp = true,
+		"mode" => {
+			opts.mode = Mode::from_name(&value).ok_or_else(|| expected(flag, "infix or rpn"))?;
+		}
+		"no-init" => opts.no_init = true,
+		"number" => {
+			opts.format.notation =
+				Notation::from_name(&value).ok_or_else(|| expected(flag, "auto, fixed or sci"))?;
+		}
+		"output" => {
+			opts.output = match value.as_str() {
+				"json" => Output::Json,
+				"text" => Output::Text,
+				_ => return Err(expected(flag, "json or text")),
+			};
+		}
+		"precision" => {
+			let precision = match value.as_str() {
+				"auto" => None,
+				_ => match value.parse::<usize>() {
+					Ok(p) if p <= MAX_PRECISION => Some(p),
+					_ => {
+						let values = format!("auto, or a number from 0 to {}", MAX_PRECISION);
+						return Err(expected(flag, &values));
+					}
+				},
+			};
+			opts.format.precision = precision;
+		}
+		"quiet" => opts.quiet = true,
+		"radix" => {
+			opts.format.radix = value
+				.parse::<u32>()
+				.ok()
+				.filter(|radix| RADIXES.contains(radix))
+				.ok_or_else(|| expected(flag, "2, 8, 10 or 16"))?;
+		}
+		"rpn" => opts.mode = Mode::Rpn,
+		"serve" => opts.serve = true,
+		#[cfg(unix)]
+		"socket" => {
+			opts.serve = true;
+			opts.socket = Some(PathBuf::from(value));
+		}
+		"template" => opts.template = true,
+		_ => return Err(format!("Unrecognized option '{}'", flag)),
+	}
+
+	Ok(())
+} // set_option
+
+// args start after the program name
+pub fn parse<I>(args: I) -> Result<Parsed, String>
+where
+	I: IntoIterator<Item = String>,
+{
+	let mut opts = Options::default();
+	let mut args = args.into_iter().peekable();
+
+	while let Some(arg) = args.next_if(|arg| arg == "--" || is_option(arg)) {
+		if arg == "--" {
+			break;
+		}
+
+		// each flag is run along with the value it was given, if any
+		let mut flags: Vec<(String, &Opt, Option<String>)> = vec![];
+
+		if let Some(name) = arg.strip_prefix("--") {
+			let (name, value) = match name.split_once('=') {
+				Some((name, value)) => (name, Some(value.to_string())),
+				None => (name, None),
+			};
+			let flag = format!("--{}", name);
+			let opt = unwrap!(OPTS.iter().find(|opt| opt.long == name), {
+				return Err(format!("Unrecognized option '{}'", flag));
+			});
+
+			let value = match (opt.value, value) {
+				(None, Some(_)) => return Err(format!("Option '{}' doesn't take a value", flag)),
+				(Some(_), None) => args.next(),
+				(_, value) => value,
+			};
+			flags.push((flag, opt, value));
+		} else {
+			// short flags may be grouped, and the last may have its value attached, e.g. -qp4
+			for (i, c) in arg.char_indices().skip(1) {
+				let flag = format!("-{}", c);
+				let opt = unwrap!(OPTS.iter().find(|opt| opt.short == Some(c)), {
+					return Err(format!("Unrecognized option '{}'; {}", flag, EXPRESSION_HINT));
+				});
+
+				if opt.value.is_some() {
+					let attached = &arg[i + c.len_utf8()..];
+					let value = if attached.is_empty() {
+						args.next()
+					} else {
+						Some(attached.to_string())
+					};
+					flags.push((flag, opt, value));
+					break;
+				}
+
+				flags.push((flag, opt, None));
+			}
+		}
+
+		for (flag, opt, value) in flags {
+			match (opt.long, opt.value, &value) {
+				("help", ..) => return Ok(Parsed::Help),
+				("version", ..) => return Ok(Parsed::Version),
+				(_, Some(name), None) => return Err(format!("Option '{}' requires {}", flag, name)),
+				_ => set_option(&mut opts, &flag, opt.long, value)
+					.map_err(|msg| format!("{}; {}", msg, EXPRESSION_HINT))?,
+			}
+		}
+	} // while
+
+	opts.inputs = args.collect();
+
+	Ok(Parsed::Run(opts))
+} // parse
+
+#[cfg(test)]
+mod tests {
+	use crate::options::*;
+
+	fn run(args: &[&str]) -> Result<Options, String> {
+		match parse(args.iter().map(|arg| arg.to_string()))? {
+			Parsed::Run(opts) => Ok(opts),
+			parsed => panic!("Unexpected {:?}", parsed),
+		}
+	}
+
+	fn inputs(args: &[&str]) -> Vec<String> {
+		run(args).unwrap().inputs
+	}
+
+	#[test]
+	fn parse_options() {
+		let opts = run(&["-qp4", "-r", "16", "--angle=deg", "--number", "sci", "-f", "a.tc", "1"]).unwrap();
+		assert!(opts.quiet);
+		assert_eq!(
+			opts.format,
+			Format {
+				notation: Notation::Scientific,
+				precision: Some(4),
+				radix: 16,
+			}
+		);
+		assert_eq!(opts.angle, Angle::Degrees);
+		assert_eq!(opts.scripts, vec![PathBuf::from("a.tc")]);
+		assert_eq!(opts.inputs, vec!["1".to_string()]);
+
+		let opts = run(&["-D", "rate=0.07", "-Dn=12", "--define=x = 1"]).unwrap();
+		assert_eq!(opts.defines, vec!["rate=0.07", "n=12", "x = 1"]);
+
+		let opts = run(&["--rpn", "--json", "--init", "my.tc", "--no-init", "--fail-fast"]).unwrap();
+		assert_eq!(opts.mode, Mode::Rpn);
+		assert_eq!(opts.output, Output::Json);
+		assert_eq!(opts.init_file, Some(PathBuf::from("my.tc")));
+		assert!(opts.no_init && opts.fail_fast);
+		assert!(run(&["-t", "a.txt"]).unwrap().template);
+
+		assert!(run(&["--serve"]).unwrap().serve);
+		assert!(run(&["--lsp"]).unwrap().lsp);
+		assert!(run(&["--explain", "6*3**2"]).unwrap().explain);
+		#[cfg(unix)]
+		{
+			let opts = run(&["--socket", "/tmp/tcalc.sock"]).unwrap();
+			assert!(opts.serve);
+			assert_eq!(opts.socket, Some(PathBuf::from("/tmp/tcalc.sock")));
+		}
+
+		assert_eq!(parse(vec!["-qh".to_string()]), Ok(Parsed::Help));
+		assert_eq!(parse(vec!["--version".to_string()]), Ok(Parsed::Version));
+	}
+
+	#[test]
+	fn parse_inputs() {
+		assert_eq!(inputs(&["-5", "--5"]), vec!["-5".to_string(), "--5".to_string()]);
+		assert_eq!(inputs(&["--", "-pi", "--json"]), vec!["-pi".to_string(), "--json".to_string()]);
+		assert_eq!(inputs(&["1", "-q"]), vec!["1".to_string(), "-q".to_string()]);
+		assert_eq!(inputs(&["-", "2"]), vec!["-".to_string(), "2".to_string()]);
+	}
+
+	#[test]
+	fn parse_errors() {
+		assert_eq!(
+			run(&["-x"]),
+			Err("Unrecognized option '-x'; put -- before expressions starting with -".to_string())
+		);
+		assert_eq!(run(&["--frobnicate"]), Err("Unrecognized option '--frobnicate'".to_string()));
+		assert_eq!(run(&["-f"]), Err("Option '-f' requires FILE".to_string()));
+		assert_eq!(
+			run(&["--json=yes"]),
+			Err("Option '--json' doesn't take a value".to_string())
+		);
+		assert_eq!(
+			run(&["-p", "20"]),
+			Err("Option '-p' expects auto, or a number from 0 to 17; put -- before expressions starting with -".to_string())
+		);
+		assert_eq!(
+			run(&["-pi"]),
+			Err("Option '-p' expects auto, or a number from 0 to 17; put -- before expressions starting with -".to_string())
+		);
+		assert_eq!(
+			run(&["--radix=3"]),
+			Err("Option '--radix' expects 2, 8, 10 or 16; put -- before expressions starting with -".to_string())
+		);
+		assert_eq!(run(&["-a", "grad"]), Err("Option '-a' expects deg or rad; put -- before expressions starting with -".to_string()));
+		assert_eq!(run(&["-o", "xml"]), Err("Option '-o' expects json or text; put -- before expressions starting with -".to_string()));
+		assert_eq!(run(&["-D", "rate"]), Err("Option '-D' expects NAME=VALUE; put -- before expressions starting with -".to_string()));
+	}
+}
@@ -1,9 +1,22 @@
 use crate::ast::*;
 use crate::buffered_iterator::*;
+use crate::builtins;
+use crate::datetime;
+use crate::math;
 use crate::scanning::*;
 
+// commands, and the keywords which begin statements
+pub static KEYWORDS: &[&str] = &[
+	"autosave", "clear", "const", "delete", "exit", "explain", "funcs", "help", "load", "quit",
+	"reset", "save", "set", "vars",
+];
+
+pub static STACK_COMMANDS: &[&str] = &["clear", "drop", "dup", "roll", "swap"];
+
 struct Parser<'a> {
+	input: &'a str,
 	scanner: BufferedIterator<Token, Scanner<'a>>,
+	errors: Vec<SyntaxError>,
 }
 
 impl<'a> Parser<'a> {
@@ -11,25 +24,46 @@ impl<'a> Parser<'a> {
 		let scanner = Scanner::new(input);
 		let buf = BufferedIterator::new(scanner);
 
-		Parser { scanner: buf }
+		Parser {
+			input,
+			scanner: buf,
+			errors: vec![],
+		}
+	}
+
+	fn error(&mut self, msg: String) {
+		self.error_at(msg, None);
 	}
 
-	fn expected_token(&self, expected: TokenType, found: &Token) {
-		println!(
-			"Expected '{:?}' but found '{:?}' instead (line {}, column {})",
-			expected, found.token_type, found.line, found.column
+	fn error_at(&mut self, msg: String, position: Option<(u32, u32)>) {
+		// scanner errors come first, since they caused the tokens to run out
+		let scan_errors = self.scanner.inner_mut().take_errors();
+		self.errors.extend(scan_errors);
+		self.errors.push(SyntaxError::new(msg, position));
+	}
+
+	fn take_errors(&mut self) -> Vec<SyntaxError> {
+		let scan_errors = self.scanner.inner_mut().take_errors();
+		self.errors.extend(scan_errors);
+		std::mem::take(&mut self.errors)
+	}
+
+	fn expected_token(&mut self, expected: TokenType, found: &Token) {
+		self.error_at(
+			format!("Expected '{:?}' but found '{:?}' instead", expected, found.token_type),
+			Some((found.line, found.column)),
 		);
 	}
 
-	fn unexpected_token(&self, found: &Token) {
-		println!(
-			"Unexpected token '{:?}' (line {}, column {})",
-			found.token_type, found.line, found.column
+	fn unexpected_token(&mut self, found: &Token) {
+		self.error_at(
+			format!("Unexpected token '{:?}'", found.token_type),
+			Some((found.line, found.column)),
 		);
 	}
 
-	fn unexpected_end_of_input(&self) {
-		println!("Unexpected end of input");
+	fn unexpected_end_of_input(&mut self) {
+		self.error("Unexpected end of input".to_string());
 	}
 
 	fn consume_token(&mut self, ttype: TokenType) -> bool {
@@ -91,24 +125,234 @@ impl<'a> Parser<'a> {
 		}
 	} // expect_terminal
 
+	// semicolons only separate statements in scripts, but commands still end
+	// at them, so e.g. "vars;" is an error rather than a variable
+	fn peek_terminal(&mut self) -> bool {
+		let t = unwrap!(self.get_token(), {
+			return true;
+		});
+
+		let terminal = matches!(t.token_type, TokenType::NewLine | TokenType::Semicolon);
+		self.put_token(t);
+
+		terminal
+	} // peek_terminal
+
 	fn parse_ast(&mut self) -> Option<Ast> {
 		trace!("parse_ast");
 
-		let ast = if let Some(cmd) = self.parse_command() {
+		let ast = self.parse_item();
+
+		if !self.expect_terminal() {
+			return None;
+		}
+
+		ast
+	}
+
+	fn parse_item(&mut self) -> Option<Ast> {
+		trace!("parse_item");
+
+		if let Some(cmd) = self.parse_command() {
 			Some(Ast::Command(cmd))
 		} else if let Some(stmt) = self.parse_statement() {
 			Some(Ast::Statement(stmt))
-		} else if let Some(expr) = self.parse_expression() {
-			Some(Ast::Expression(expr))
 		} else {
-			None
-		};
+			self.parse_expression().map(Ast::Expression)
+		}
+	}
 
-		if !self.expect_terminal() {
-			return None;
+	// each statement is paired with the line it starts on and its source text
+	fn parse_script(&mut self) -> Vec<(u32, &'a str, Result<Ast, SyntaxError>)> {
+		trace!("parse_script");
+
+		let mut asts = vec![];
+		loop {
+			let t = match self.get_token() {
+				Some(t) if matches!(t.token_type, TokenType::NewLine | TokenType::Semicolon) => {
+					continue;
+				}
+				Some(t) => t,
+				None => {
+					// the scanner stops at characters it cannot scan, so carry on
+					// from the next line
+					let line = self.scanner.inner_mut().line();
+					match self.take_errors().into_iter().next() {
+						Some(e) => {
+							let scanner = self.scanner.inner_mut();
+							let start = self.input[..scanner.offset()].rfind('\n').map_or(0, |i| i + 1);
+							scanner.skip_line();
+							let text = self.input[start..scanner.offset()].trim();
+							asts.push((line, text, Err(e)));
+							continue;
+						}
+						None => break,
+					}
+				}
+			};
+
+			let (line, start) = (t.line, t.offset);
+			self.put_token(t);
+
+			// a failed statement is not checked for its separator, which could take
+			// a token from the next line
+			let ast = self.parse_item();
+			let failed = !self.errors.is_empty() || self.scanner.inner_mut().has_errors();
+			let end = if failed { None } else { self.expect_separator() };
+
+			match (self.take_errors().into_iter().next(), ast, end) {
+				(None, Some(ast), Some(end)) => asts.push((line, self.input[start..end].trim(), Ok(ast))),
+				(e, _, _) => {
+					let e = e.unwrap_or_else(|| SyntaxError::new("Unexpected end of input".to_string(), None));
+					let end = self.skip_statement(e.position.map_or(line, |(line, _)| line));
+					asts.push((line, self.input[start..end].trim(), Err(e)));
+				}
+			}
 		}
 
-		ast
+		asts
+	} // parse_script
+
+	// returns where the statement ends, or None if it is not followed by a separator
+	fn expect_separator(&mut self) -> Option<usize> {
+		let t = unwrap!(self.get_token(), {
+			return Some(self.scanner.inner_mut().offset());
+		});
+
+		match t.token_type {
+			TokenType::NewLine | TokenType::Semicolon => Some(t.offset),
+			_ => {
+				self.unexpected_token(&t);
+				None
+			}
+		}
+	}
+
+	// skips the rest of a statement which failed to parse, up to the end of the
+	// line with the error, so the next one can still be parsed; unclosed parens
+	// are forgotten, or they would swallow every line after it. Returns where
+	// the skipped statement ends
+	fn skip_statement(&mut self, line: u32) -> usize {
+		self.scanner.inner_mut().end_continuation();
+
+		loop {
+			match self.get_token() {
+				Some(t) if matches!(t.token_type, TokenType::NewLine | TokenType::Semicolon) => {
+					return t.offset
+				}
+				// read ahead past a new line which was taken as a continuation
+				Some(t) if t.line > line => {
+					let offset = t.offset;
+					self.put_token(t);
+					return offset;
+				}
+				Some(_) => {}
+				None => {
+					// the scanner stops at characters it cannot scan, which were
+					// already reported
+					let scanner = self.scanner.inner_mut();
+					if scanner.has_errors() {
+						scanner.take_errors();
+						scanner.skip_line();
+					}
+					return scanner.offset();
+				}
+			}
+		}
+	} // skip_statement
+
+	fn parse_rpn(&mut self) -> Option<Vec<RpnItem>> {
+		trace!("parse_rpn");
+
+		let mut items = vec![];
+		while let Some(t) = self.get_token() {
+			let op = match t.token_type {
+				TokenType::Ampersand => Some(BinaryOp::BitAnd),
+				TokenType::Caret => Some(BinaryOp::BitXor),
+				TokenType::ForwardSlash => Some(BinaryOp::Divide),
+				TokenType::LeftAngleBracketX2 => Some(BinaryOp::LeftShift),
+				TokenType::Minus if !self.peek_adjacent_literal(&t) => Some(BinaryOp::Minus),
+				TokenType::Percent => Some(BinaryOp::Modulo),
+				TokenType::Pipe => Some(BinaryOp::BitOr),
+				TokenType::Plus => Some(BinaryOp::Plus),
+				TokenType::RightAngleBracketX2 => Some(BinaryOp::RightShift),
+				TokenType::Star => Some(BinaryOp::Multiply),
+				TokenType::StarX2 => Some(BinaryOp::Exponent),
+				_ => None,
+			};
+
+			if let Some(op) = op {
+				items.push(RpnItem::Binary(op));
+				continue;
+			}
+
+			let item = match t.token_type {
+				TokenType::Bang => RpnItem::Unary(UnaryOp::Factorial),
+				// a minus sign touching a number is part of it, e.g. -4
+				TokenType::Minus => RpnItem::Operand(Expression::Unary(Unary {
+					op: UnaryOp::Negate,
+					right: Box::new(self.parse_primary()?),
+				})),
+				TokenType::NewLine => continue,
+				TokenType::Identifier { ref str } if STACK_COMMANDS.contains(&str.as_str()) => {
+					RpnItem::Stack(str.clone())
+				}
+				TokenType::Identifier { ref str } if str == "neg" => RpnItem::Unary(UnaryOp::Negate),
+				TokenType::Identifier { ref str } if str == "not" => RpnItem::Unary(UnaryOp::Not),
+				TokenType::Identifier { ref str } if builtins::find(str).is_some() => {
+					// builtins may still be called with parens, e.g. abs(-4)
+					match self.get_token() {
+						Some(next) if next.token_type == TokenType::LeftParen => {
+							self.put_token(next);
+							self.put_token(t);
+							RpnItem::Operand(self.parse_primary()?)
+						}
+						next => {
+							if let Some(next) = next {
+								self.put_token(next);
+							}
+							RpnItem::Call(str.clone())
+						}
+					}
+				}
+				TokenType::Date { .. }
+				| TokenType::Duration { .. }
+				| TokenType::Identifier { .. }
+				| TokenType::LeftParen
+				| TokenType::Number { .. }
+				| TokenType::ResultRef { .. }
+				| TokenType::String { .. } => {
+					self.put_token(t);
+					RpnItem::Operand(self.parse_primary()?)
+				}
+				_ => {
+					self.unexpected_token(&t);
+					return None;
+				}
+			};
+
+			items.push(item);
+		}
+
+		Some(items)
+	} // parse_rpn
+
+	// whether the next token is a number or duration directly after t, with no
+	// space between them
+	fn peek_adjacent_literal(&mut self, t: &Token) -> bool {
+		let next = unwrap!(self.get_token(), {
+			return false;
+		});
+
+		let adjacent = next.line == t.line
+			&& next.column == t.column + t.length
+			&& matches!(
+				next.token_type,
+				TokenType::Number { .. } | TokenType::Duration { .. }
+			);
+		self.put_token(next);
+
+		adjacent
 	}
 
 	fn parse_command(&mut self) -> Option<Command> {
@@ -121,9 +365,81 @@ impl<'a> Parser<'a> {
 			..
 		} = t
 		{
-			match str.as_str() {
-				"exit" | "quit" => return Some(Command::Exit),
-				_ => {}
+			// file names and help topics are taken verbatim from the rest of the line,
+			// unless it continues an expression, like "save = 2"
+			if matches!(str.as_str(), "help" | "load" | "save") {
+				let rest = self.scanner.inner_mut().rest_of_line();
+				if continues_expression(str, &rest) {
+					self.scanner.inner_mut().put_back(&rest);
+				} else {
+					let arg = match rest.trim() {
+						"" => None,
+						arg => Some(arg.to_string()),
+					};
+
+					return Some(match str.as_str() {
+						"help" => Command::Help(arg),
+						"load" => Command::Load(arg),
+						_ => Command::Save(arg),
+					});
+				}
+			}
+
+			if str == "autosave" {
+				let arg = self.get_token();
+				if let Some(Token {
+					token_type: TokenType::Identifier { str: ref on_off },
+					..
+				}) = arg
+				{
+					if (on_off == "on" || on_off == "off") && self.peek_terminal() {
+						return Some(Command::Autosave(Some(on_off == "on")));
+					}
+				}
+
+				if let Some(arg) = arg {
+					self.put_token(arg);
+				}
+			}
+
+			// like commands, "set = 2" is still an assignment
+			if str == "set" {
+				match self.get_token() {
+					Some(arg) if matches!(arg.token_type, TokenType::Identifier { .. }) => {
+						self.put_token(arg);
+						return self.parse_setting().map(Command::Set);
+					}
+					Some(arg) => self.put_token(arg),
+					None => {}
+				}
+			}
+
+			// explain takes an expression, but "explain = 2" or "explain * 2" is
+			// still the variable
+			if str == "explain" {
+				match self.get_token() {
+					Some(arg) if starts_operand(&arg.token_type) => {
+						self.put_token(arg);
+						return self.parse_expression().map(Command::Explain);
+					}
+					Some(arg) => self.put_token(arg),
+					None => {}
+				}
+			}
+
+			let cmd = match str.as_str() {
+				"autosave" => Some(Command::Autosave(None)),
+				"clear" => Some(Command::Clear),
+				"exit" | "quit" => Some(Command::Exit),
+				"funcs" => Some(Command::Funcs),
+				"reset" => Some(Command::Reset),
+				"vars" => Some(Command::Vars),
+				_ => None,
+			};
+
+			// commands stand alone, so e.g. "vars = 2" is still an assignment
+			if cmd.is_some() && self.peek_terminal() {
+				return cmd;
 			}
 		}
 
@@ -132,22 +448,123 @@ impl<'a> Parser<'a> {
 		None
 	} // parse_command
 
+	fn parse_setting(&mut self) -> Option<Setting> {
+		trace!("parse_setting");
+
+		let mut words = vec![];
+		while let Some(t) = self.get_token() {
+			match t.token_type {
+				TokenType::Identifier { str } => words.push(str),
+				TokenType::Number { str, prefix } => words.push(prefix + &str),
+				TokenType::NewLine | TokenType::Semicolon => {
+					self.put_token(t);
+					break;
+				}
+				_ => {
+					self.unexpected_token(&t);
+					return None;
+				}
+			}
+		}
+
+		let (name, value) = match &words[..] {
+			[name, value] => (name.as_str(), Some(value.as_str())),
+			[name, ..] => (name.as_str(), None),
+			[] => ("", None),
+		};
+
+		let setting = match name {
+			"angle" => value.and_then(Angle::from_name).map(Setting::Angle),
+			"mode" => value.and_then(Mode::from_name).map(Setting::Mode),
+			"number" => value.and_then(Notation::from_name).map(Setting::Notation),
+			"precision" => match value {
+				Some("auto") => Some(Setting::Precision(None)),
+				Some(value) => value
+					.parse::<usize>()
+					.ok()
+					.filter(|p| *p <= MAX_PRECISION)
+					.map(|p| Setting::Precision(Some(p))),
+				None => None,
+			},
+			"radix" => value
+				.and_then(|value| value.parse::<u32>().ok())
+				.filter(|radix| RADIXES.contains(radix))
+				.map(Setting::Radix),
+			_ => {
+				self.error(format!("Unknown setting \"{}\"", words.join(" ")));
+				return None;
+			}
+		};
+
+		if setting.is_none() {
+			let expected = match name {
+				"angle" => "\"set angle deg\" or \"set angle rad\"".to_string(),
+				"mode" => "\"set mode infix\" or \"set mode rpn\"".to_string(),
+				"number" => "\"set number\" followed by auto, fixed or sci".to_string(),
+				"precision" => format!(
+					"\"set precision\" followed by auto, or a number from 0 to {}",
+					MAX_PRECISION
+				),
+				_ => "\"set radix\" followed by 2, 8, 10 or 16".to_string(),
+			};
+			self.error(format!("Expected {}", expected));
+		}
+
+		setting
+	} // parse_setting
+
 	fn parse_statement(&mut self) -> Option<Statement> {
 		trace!("parse_statement");
 
 		let t = self.get_token()?;
 
+		// $n = value restores a numbered result, e.g. from a saved session
+		if let TokenType::ResultRef { ref str } = t.token_type {
+			if let Ok(number) = str.parse::<usize>() {
+				if self.consume_token(TokenType::Equal) {
+					let right = unwrap!(self.parse_expression(), {
+						self.error(format!("Missing right-hand side of result ${}", number));
+						return None;
+					});
+
+					return Some(Statement::SetResult(number, right));
+				}
+			}
+		}
+
 		if let Token {
 			token_type: TokenType::Identifier { ref str, .. },
 			..
 		} = t
 		{
-			if let "delete" = str.as_str() {
-				let tvar = self.expect_any_token()?;
-				if let TokenType::Identifier { str } = tvar.token_type {
-					return Some(Statement::DeleteVar(Variable { name: str }));
+			match str.as_str() {
+				"const" => {
+					let tvar = self.expect_any_token()?;
+					if let TokenType::Identifier { str } = tvar.token_type {
+						if !self.expect_token(TokenType::Equal) {
+							return None;
+						}
+
+						let right = unwrap!(self.parse_expression(), {
+							self.error(format!("Missing right-hand side of constant \"{}\"", str));
+							return None;
+						});
+
+						return Some(Statement::Const(Assignment {
+							var: Variable { name: str },
+							right: Box::new(right),
+						}));
+					}
+					self.put_token(tvar);
+				}
+				"delete" => {
+					let tvar = self.expect_any_token()?;
+					if let TokenType::Identifier { str } = tvar.token_type {
+						return Some(Statement::DeleteVar(Variable { name: str }));
+					}
+					self.put_token(tvar);
 				}
-				self.put_token(tvar);
+				_ => {}
 			}
 		}
 
@@ -215,7 +632,7 @@ impl<'a> Parser<'a> {
 
 		// parse the right-hand expression
 		let mut right = unwrap!(right_opt, {
-			println!("Missing right-hand side of assignment to \"{}\"", var.name);
+			self.error(format!("Missing right-hand side of assignment to \"{}\"", var.name));
 			return None;
 		});
 
@@ -383,25 +800,39 @@ impl<'a> Parser<'a> {
 
 		let t = self.expect_any_token()?;
 
-		let op;
-		match t.token_type {
-			TokenType::Minus => op = UnaryOp::Negate,
-			TokenType::Bang => op = UnaryOp::Not,
+		// a leading '!' is always bitwise NOT; a trailing '!' is factorial
+		let op = match t.token_type {
+			TokenType::Minus => UnaryOp::Negate,
+			TokenType::Bang => UnaryOp::Not,
 			_ => {
 				self.put_token(t);
-				return self.parse_primary();
+				return self.parse_postfix();
 			}
-		}
+		};
 
-		match self.parse_unary() {
-			Some(right) => Some(Expression::Unary(Unary {
+		self.parse_unary().map(|right| {
+			Expression::Unary(Unary {
 				op,
 				right: Box::new(right),
-			})),
-			None => None,
-		}
+			})
+		})
 	} // parse_unary
 
+	fn parse_postfix(&mut self) -> Option<Expression> {
+		trace!("parse_postfix");
+
+		let mut expr = self.parse_primary()?;
+
+		while self.consume_token(TokenType::Bang) {
+			expr = Expression::Unary(Unary {
+				op: UnaryOp::Factorial,
+				right: Box::new(expr),
+			});
+		}
+
+		Some(expr)
+	} // parse_postfix
+
 	fn parse_primary(&mut self) -> Option<Expression> {
 		trace!("parse_primary");
 
@@ -417,26 +848,66 @@ impl<'a> Parser<'a> {
 					_ => 10,
 				};
 
+				// integers are read exactly, when they fit in 128 bits
+				if let Ok(n) = i128::from_str_radix(str.as_str(), radix) {
+					return Some(Expression::Literal(integer_literal(n)));
+				}
+
 				// rust core does not currently support parsing non-base-10 decimal numbers
 				if radix == 10 {
 					match str.parse::<f64>() {
 						Ok(n) => Some(Expression::Literal(Literal::Number(n))),
 						Err(msg) => {
-							println!("Failed to parse number \"{}{}\": {}", prefix, str, msg);
+							self.error(format!("Failed to parse number \"{}{}\": {}", prefix, str, msg));
 							None
 						}
 					}
 				} else {
-					match u64::from_str_radix(str.as_str(), radix) {
+					match u128::from_str_radix(str.as_str(), radix) {
 						Ok(n) => Some(Expression::Literal(Literal::Number(n as f64))),
 						Err(msg) => {
-							println!("Failed to parse number \"{}{}\": {}", prefix, str, msg);
+							self.error(format!("Failed to parse number \"{}{}\": {}", prefix, str, msg));
 							None
 						}
 					}
 				}
 			}
+			TokenType::Date { str } => match datetime::parse_date(&str) {
+				Ok(millis) => Some(Expression::Literal(Literal::Date(millis))),
+				Err(msg) => {
+					self.error(msg);
+					None
+				}
+			},
+			TokenType::Duration { str, unit } => {
+				let mut millis = self.parse_duration(&str, &unit)?;
+
+				// adjacent durations are summed, e.g. 3d 4h
+				while let Some(t) = self.get_token() {
+					if let TokenType::Duration { str, unit } = t.token_type {
+						millis = unwrap!(millis.checked_add(self.parse_duration(&str, &unit)?), {
+							self.error("Duration is too large".to_string());
+							return None;
+						});
+					} else {
+						self.put_token(t);
+						break;
+					}
+				}
+
+				Some(Expression::Literal(Literal::Duration(millis)))
+			}
+			TokenType::String { str } => Some(Expression::Literal(Literal::String(str))),
 			TokenType::Identifier { str } => {
+				if str == "ans" && self.consume_token(TokenType::LeftBracket) {
+					let offset = self.parse_expression()?;
+					if !self.expect_token(TokenType::RightBracket) {
+						return None;
+					}
+
+					return Some(Expression::Result(ResultRef::Offset(Box::new(offset))));
+				}
+
 				let call = self.parse_call(str.clone());
 				if call.is_some() {
 					call
@@ -444,6 +915,19 @@ impl<'a> Parser<'a> {
 					Some(Expression::Variable(Variable { name: str }))
 				}
 			}
+			TokenType::ResultRef { str } => {
+				if str == "$" {
+					return Some(Expression::Result(ResultRef::Latest));
+				}
+
+				match str.parse::<usize>() {
+					Ok(n) => Some(Expression::Result(ResultRef::Number(n))),
+					Err(msg) => {
+						self.error(format!("Failed to parse result \"${}\": {}", str, msg));
+						None
+					}
+				}
+			}
 			TokenType::LeftParen => {
 				let expr = self.parse_expression();
 				if self.expect_token(TokenType::RightParen) {
@@ -458,10 +942,177 @@ impl<'a> Parser<'a> {
 			}
 		} // match
 	} // parse_primary
+
+	fn parse_duration(&mut self, str: &str, unit: &str) -> Option<i64> {
+		let unit_millis = datetime::unit_millis(unit)?;
+
+		match str.parse::<f64>() {
+			Ok(n) if (n * unit_millis as f64) < datetime::MAX_DURATION_MILLIS as f64 => {
+				Some((n * unit_millis as f64).round() as i64)
+			}
+			Ok(_) => {
+				self.error(format!("Duration \"{}{}\" is too large", str, unit));
+				None
+			}
+			Err(msg) => {
+				self.error(format!("Failed to parse duration \"{}{}\": {}", str, unit, msg));
+				None
+			}
+		}
+	} // parse_duration
 } // Parser
 
+// integers an f64 holds exactly are Numbers, like those in other expressions
+fn integer_literal(n: i128) -> Literal {
+	if n.unsigned_abs() < math::MAX_EXACT_INT as u128 {
+		Literal::Number(n as f64)
+	} else {
+		Literal::Integer(n)
+	}
+}
+
+// whether the token can start an operand, like a number or an opening paren
+fn starts_operand(token_type: &TokenType) -> bool {
+	matches!(
+		token_type,
+		TokenType::Bang
+			| TokenType::Date { .. }
+			| TokenType::Duration { .. }
+			| TokenType::Identifier { .. }
+			| TokenType::LeftParen
+			| TokenType::Minus
+			| TokenType::Number { .. }
+			| TokenType::ResultRef { .. }
+			| TokenType::String { .. }
+	)
+}
+
+// whether the rest of a line after a command continues an expression, like
+// "= 2" or "* 2", rather than being a file name or help topic, like "/tmp/a.tc"
+// or "**"; help topics and paths may start with an operator, so for help, and
+// for "/", binary operators only continue one when a space follows them
+pub fn continues_expression(command: &str, rest: &str) -> bool {
+	let rest = rest.trim_start();
+	let t = unwrap!(Scanner::new(rest).next(), {
+		return false;
+	});
+
+	match t.token_type {
+		TokenType::AmpersandEqual
+		| TokenType::CaretEqual
+		| TokenType::Equal
+		| TokenType::ForwardSlashEqual
+		| TokenType::LeftAngleBracketX2Equal
+		| TokenType::MinusEqual
+		| TokenType::MinusX2
+		| TokenType::PercentEqual
+		| TokenType::PipeEqual
+		| TokenType::PlusEqual
+		| TokenType::PlusX2
+		| TokenType::RightAngleBracketX2Equal
+		| TokenType::StarEqual
+		| TokenType::StarX2Equal => true,
+		TokenType::Ampersand
+		| TokenType::Caret
+		| TokenType::ForwardSlash
+		| TokenType::LeftAngleBracketX2
+		| TokenType::Minus
+		| TokenType::Percent
+		| TokenType::Pipe
+		| TokenType::Plus
+		| TokenType::RightAngleBracketX2
+		| TokenType::Star
+		| TokenType::StarX2 => {
+			let after = &rest[t.length as usize..];
+			let spaced = command == "help" || t.token_type == TokenType::ForwardSlash;
+			(!spaced || after.starts_with(char::is_whitespace)) && !after.trim().is_empty()
+		}
+		_ => false,
+	}
+} // continues_expression
+
+pub fn is_incomplete(input: &str) -> bool {
+	let mut scanner = Scanner::new(input);
+
+	// arguments taken verbatim, like file names, are always complete
+	match scanner.next() {
+		Some(Token {
+			token_type: TokenType::Identifier { ref str },
+			..
+		}) if matches!(str.as_str(), "help" | "load" | "save") => {
+			let rest = scanner.rest_of_line();
+			if !continues_expression(str, &rest) {
+				return false;
+			}
+			scanner.put_back(&rest);
+		}
+		None => return false,
+		_ => {}
+	}
+
+	while scanner.next().is_some() {}
+
+	// errors other than running out of input are not fixed by more lines
+	scanner.take_errors().is_empty()
+		&& scanner.is_incomplete()
+		&& parse_script_located(input)
+			.into_iter()
+			.all(|(_, ast)| ast.map_or_else(|e| e.position.is_none(), |_| true))
+}
+
+pub fn try_parse_located(input: &str) -> Result<Option<Ast>, SyntaxError> {
+	let mut parser = Parser::new(input);
+	let ast = parser.parse_ast();
+
+	match parser.take_errors().into_iter().next() {
+		Some(e) => Err(e),
+		None => Ok(ast),
+	}
+}
+
+pub fn try_parse(input: &str) -> Result<Option<Ast>, String> {
+	try_parse_located(input).map_err(|e| e.to_string())
+}
+
+pub fn try_parse_rpn(input: &str) -> Result<Vec<RpnItem>, String> {
+	let mut parser = Parser::new(input);
+	let items = parser.parse_rpn();
+
+	match parser.take_errors().into_iter().next() {
+		Some(e) => Err(e.to_string()),
+		None => items.ok_or_else(|| "Unexpected end of input".to_string()),
+	}
+}
+
+// statements are separated by new lines or semicolons, and paired with the
+// line each starts on
+pub fn parse_script(input: &str) -> Vec<(u32, Result<Ast, String>)> {
+	parse_script_located(input)
+		.into_iter()
+		.map(|(line, ast)| (line, ast.map_err(|e| e.to_string())))
+		.collect()
+}
+
+pub fn parse_script_located(input: &str) -> Vec<(u32, Result<Ast, SyntaxError>)> {
+	parse_script_sourced(input)
+		.into_iter()
+		.map(|(line, _, ast)| (line, ast))
+		.collect()
+}
+
+// also with the source text of each statement
+pub fn parse_script_sourced(input: &str) -> Vec<(u32, &str, Result<Ast, SyntaxError>)> {
+	Parser::new(input).parse_script()
+}
+
 pub fn parse(input: &str) -> Option<Ast> {
-	Parser::new(input).parse_ast()
+	match try_parse(input) {
+		Ok(ast) => ast,
+		Err(msg) => {
+			println!("{}", msg);
+			None
+		}
+	}
 }
 
 #[cfg(test)]
@@ -561,6 +1212,27 @@ mod tests {
 		expect("12345.67890", num_ast(12345.6789f64));
 	}
 
+	#[test]
+	fn parse_date() {
+		expect(
+			"1970-01-02",
+			Ast::Expression(Expression::Literal(Literal::Date(86_400_000))),
+		);
+		expect(
+			"2026-10-18T12:00Z",
+			Ast::Expression(Expression::Literal(Literal::Date(1_792_324_800_000))),
+		);
+		assert_eq!(parse("2026-02-30"), None);
+	}
+
+	#[test]
+	fn parse_duration() {
+		let duration = |ms| Ast::Expression(Expression::Literal(Literal::Duration(ms)));
+		expect("90min", duration(5_400_000));
+		expect("3d 4h", duration(273_600_000));
+		expect("1.5s", duration(1_500));
+	}
+
 	#[test]
 	fn parse_variable() {
 		expect("e", var_ast("e"));
@@ -582,6 +1254,40 @@ mod tests {
 		expect("!e", uni_op_ast(UnaryOp::Not, var_expr("e")));
 	}
 
+	#[test]
+	fn parse_factorial() {
+		expect("5!", uni_op_ast(UnaryOp::Factorial, num_expr(5f64)));
+		expect(
+			"3!!",
+			uni_op_ast(
+				UnaryOp::Factorial,
+				uni_op_expr(UnaryOp::Factorial, num_expr(3f64)),
+			),
+		);
+		expect(
+			"!3!",
+			uni_op_ast(
+				UnaryOp::Not,
+				uni_op_expr(UnaryOp::Factorial, num_expr(3f64)),
+			),
+		);
+		expect(
+			"-a!",
+			uni_op_ast(
+				UnaryOp::Negate,
+				uni_op_expr(UnaryOp::Factorial, var_expr("a")),
+			),
+		);
+		expect(
+			"2**3!",
+			bin_op_ast(
+				num_expr(2f64),
+				BinaryOp::Exponent,
+				uni_op_expr(UnaryOp::Factorial, num_expr(3f64)),
+			),
+		);
+	}
+
 	fn parse_bin_op(str_op: &str, op: BinaryOp) {
 		expect(
 			&format!("2 {} 7", str_op),
@@ -775,8 +1481,227 @@ mod tests {
 
 	#[test]
 	fn parse_command() {
+		expect("clear", Ast::Command(Command::Clear));
 		expect("exit", Ast::Command(Command::Exit));
+		expect("funcs", Ast::Command(Command::Funcs));
 		expect("quit", Ast::Command(Command::Exit));
+		expect("reset", Ast::Command(Command::Reset));
+		expect("vars", Ast::Command(Command::Vars));
+		expect("vars = 2", assign_ast("vars", num_expr(2f64)));
+		expect("save = 2", assign_ast("save", num_expr(2f64)));
+		expect("load += 1", comp_assign_ast("load", BinaryOp::Plus, num_expr(1f64)));
+		expect("save * 2", bin_op_ast(var_expr("save"), BinaryOp::Multiply, num_expr(2f64)));
+		expect("save*2", bin_op_ast(var_expr("save"), BinaryOp::Multiply, num_expr(2f64)));
+		expect("load+1", bin_op_ast(var_expr("load"), BinaryOp::Plus, num_expr(1f64)));
+		expect("help*2", Ast::Command(Command::Help(Some("*2".to_string()))));
+		expect(
+			"explain 6*3**2",
+			Ast::Command(Command::Explain(bin_op_expr(
+				num_expr(6f64),
+				BinaryOp::Multiply,
+				bin_op_expr(num_expr(3f64), BinaryOp::Exponent, num_expr(2f64)),
+			))),
+		);
+		expect(
+			"explain x = -1",
+			Ast::Command(Command::Explain(assign_expr(
+				"x",
+				uni_op_expr(UnaryOp::Negate, num_expr(1f64)),
+			))),
+		);
+		expect("explain = 2", assign_ast("explain", num_expr(2f64)));
+		expect(
+			"explain * 2",
+			bin_op_ast(var_expr("explain"), BinaryOp::Multiply, num_expr(2f64)),
+		);
+		expect("reset\n", Ast::Command(Command::Reset));
+		expect("autosave", Ast::Command(Command::Autosave(None)));
+		expect("autosave on", Ast::Command(Command::Autosave(Some(true))));
+		expect("autosave off", Ast::Command(Command::Autosave(Some(false))));
+		assert_eq!(parse("autosave on = 1"), None);
+		expect("save", Ast::Command(Command::Save(None)));
+		expect("help", Ast::Command(Command::Help(None)));
+		expect("help **", Ast::Command(Command::Help(Some("**".to_string()))));
+		expect("help = 2", assign_ast("help", num_expr(2f64)));
+		expect(
+			"save ~/my session.tc ",
+			Ast::Command(Command::Save(Some("~/my session.tc".to_string()))),
+		);
+		expect(
+			"save /tmp/a.tc",
+			Ast::Command(Command::Save(Some("/tmp/a.tc".to_string()))),
+		);
+		expect(
+			"load ../a.tc\n",
+			Ast::Command(Command::Load(Some("../a.tc".to_string()))),
+		);
+	}
+
+	#[test]
+	fn parse_multi_line() {
+		expect(
+			"a = gcd(\n\t12,\n\t18\n) +\n1\n",
+			assign_ast(
+				"a",
+				bin_op_expr(
+					call_expr("gcd", vec![num_expr(12f64), num_expr(18f64)]),
+					BinaryOp::Plus,
+					num_expr(1f64),
+				),
+			),
+		);
+		assert!(is_incomplete("a = gcd(\n12,"));
+		assert!(is_incomplete("1 +"));
+		assert!(is_incomplete("const a ="));
+		assert!(!is_incomplete("(1 + 2)"));
+		assert!(!is_incomplete("5!"));
+		assert!(!is_incomplete("(1 $"));
+		assert!(!is_incomplete("save a("));
+		assert!(is_incomplete("save = 2 *"));
+		assert!(is_incomplete("save*2 +"));
+		assert!(is_incomplete("1; (2 +"));
+		assert!(!is_incomplete("abs(1 2"));
+		assert!(!is_incomplete("1 +* 2 +"));
+		assert!(!is_incomplete(""));
+	}
+
+	#[test]
+	fn parse_result_ref() {
+		let result = |r| Ast::Expression(Expression::Result(r));
+		expect("$$", result(ResultRef::Latest));
+		expect("$3", result(ResultRef::Number(3)));
+		expect(
+			"ans[-2]",
+			result(ResultRef::Offset(Box::new(Expression::Unary(Unary {
+				op: UnaryOp::Negate,
+				right: Box::new(num_expr(2f64)),
+			})))),
+		);
+		expect(
+			"$3 = 4",
+			Ast::Statement(Statement::SetResult(3, num_expr(4f64))),
+		);
+		assert_eq!(parse("ans[-2"), None);
+		assert_eq!(parse("$"), None);
+	}
+
+	#[test]
+	fn parse_scripts() {
+		let asts = parse_script("#!/usr/bin/env tcalc\na = 1; b = $ 2\n\n// sum\nvars; a +\n\tb\n)\n4");
+		assert_eq!(
+			asts,
+			vec![
+				(2, Ok(assign_ast("a", num_expr(1f64)))),
+				(2, Err("Unexpected character '$' (line 2, column 12)".to_string())),
+				(5, Ok(Ast::Command(Command::Vars))),
+				(
+					5,
+					Ok(Ast::Expression(Expression::Binary(Binary {
+						left: Box::new(Expression::Variable(var("a"))),
+						op: BinaryOp::Plus,
+						right: Box::new(Expression::Variable(var("b"))),
+					})))
+				),
+				(7, Err("Unexpected token 'RightParen' (line 7, column 1)".to_string())),
+				(8, Ok(Ast::Expression(num_expr(4f64)))),
+			]
+		);
+		assert_eq!(
+			parse_script("(1 +"),
+			vec![(1, Err("Unexpected end of input".to_string()))]
+		);
+		assert_eq!(
+			parse_script("f(1 2\nx = 3\nx"),
+			vec![
+				(
+					1,
+					Err("Expected 'RightParen' but found 'Number { str: \"2\", prefix: \"\" }' instead (line 1, column 5)"
+						.to_string())
+				),
+				(2, Ok(assign_ast("x", num_expr(3f64)))),
+				(3, Ok(Ast::Expression(Expression::Variable(var("x"))))),
+			]
+		);
+		assert_eq!(try_parse("1; 2"), Err("Unexpected token 'Semicolon' (line 1, column 2)".to_string()));
+
+		let texts: Vec<&str> = parse_script_sourced("a = 1; b = $ 2\n\nvars; a + // sum\n\tb\n)\nf(1 2\nx\n4")
+			.into_iter()
+			.map(|(_, text, _)| text)
+			.collect();
+		assert_eq!(texts, vec!["a = 1", "b = $ 2", "vars", "a + // sum\n\tb", ")", "f(1 2", "x", "4"]);
+	}
+
+	#[test]
+	fn parse_set() {
+		expect("set mode rpn", Ast::Command(Command::Set(Setting::Mode(Mode::Rpn))));
+		expect("set mode infix", Ast::Command(Command::Set(Setting::Mode(Mode::Infix))));
+		expect("set = 2", assign_ast("set", num_expr(2f64)));
+		assert_eq!(
+			try_parse("set mode polish"),
+			Err("Expected \"set mode infix\" or \"set mode rpn\"".to_string())
+		);
+		assert_eq!(try_parse("set color on"), Err("Unknown setting \"color on\"".to_string()));
+		expect("set angle deg", Ast::Command(Command::Set(Setting::Angle(Angle::Degrees))));
+		expect("set number sci", Ast::Command(Command::Set(Setting::Notation(Notation::Scientific))));
+		expect("set precision 4", Ast::Command(Command::Set(Setting::Precision(Some(4)))));
+		expect("set precision auto", Ast::Command(Command::Set(Setting::Precision(None))));
+		expect("set radix 16", Ast::Command(Command::Set(Setting::Radix(16))));
+		assert_eq!(
+			try_parse("set precision 18"),
+			Err("Expected \"set precision\" followed by auto, or a number from 0 to 17".to_string())
+		);
+		assert_eq!(
+			try_parse("set radix 3"),
+			Err("Expected \"set radix\" followed by 2, 8, 10 or 16".to_string())
+		);
+	}
+
+	#[test]
+	fn parse_rpn_line() {
+		assert_eq!(
+			try_parse_rpn("3 -4.5 -\nabs swap"),
+			Ok(vec![
+				RpnItem::Operand(num_expr(3f64)),
+				RpnItem::Operand(Expression::Unary(Unary {
+					op: UnaryOp::Negate,
+					right: Box::new(num_expr(4.5f64)),
+				})),
+				RpnItem::Binary(BinaryOp::Minus),
+				RpnItem::Call("abs".to_string()),
+				RpnItem::Stack("swap".to_string()),
+			])
+		);
+		assert_eq!(
+			try_parse_rpn("x 5! neg"),
+			Ok(vec![
+				RpnItem::Operand(Expression::Variable(var("x"))),
+				RpnItem::Operand(num_expr(5f64)),
+				RpnItem::Unary(UnaryOp::Factorial),
+				RpnItem::Unary(UnaryOp::Negate),
+			])
+		);
+		assert_eq!(
+			try_parse_rpn("1 ,"),
+			Err("Unexpected token 'Comma' (line 1, column 3)".to_string())
+		);
+	}
+
+	#[test]
+	fn parse_const() {
+		expect(
+			"const a = 8",
+			Ast::Statement(Statement::Const(Assignment {
+				var: var("a"),
+				right: Box::new(num_expr(8f64)),
+			})),
+		);
+		assert_eq!(parse("const a"), None);
+		assert_eq!(parse("const a ="), None);
+	}
+
+	#[test]
+	fn parse_delete() {
+		expect("delete a", Ast::Statement(Statement::DeleteVar(var("a"))));
 	}
 
 	#[test]
@@ -0,0 +1,234 @@
+// reverse Polish notation, e.g. "3 4 + 2 *"
+
+use crate::ast::*;
+use crate::builtins;
+use crate::parsing;
+use crate::running::*;
+use crate::value::*;
+
+// clear is a stack command in RPN mode, rather than the REPL command
+pub fn command(line: &str) -> Option<Command> {
+	match parsing::try_parse(line) {
+		Ok(Some(Ast::Command(cmd))) if cmd != Command::Clear => Some(cmd),
+		_ => None,
+	}
+}
+
+pub fn is_word(name: &str) -> bool {
+	parsing::STACK_COMMANDS.contains(&name)
+		|| matches!(name, "neg" | "not")
+		|| builtins::find(name).is_some()
+}
+
+// pops count values, returning them from the deepest to the top of the stack
+fn pop(values: &mut Vec<Value>, count: usize, name: &str) -> Result<Vec<Value>, String> {
+	if values.len() < count {
+		return Err(format!(
+			"'{}' expects {} values on the stack, but found {}",
+			name,
+			count,
+			values.len()
+		));
+	}
+
+	Ok(values.split_off(values.len() - count))
+}
+
+fn pop_one(values: &mut Vec<Value>, name: &str) -> Result<Value, String> {
+	Ok(pop(values, 1, name)?.remove(0))
+}
+
+#[derive(Clone, Default)]
+pub struct Stack {
+	values: Vec<Value>,
+}
+
+impl Stack {
+	pub fn new() -> Stack {
+		Stack::default()
+	}
+
+	// from the bottom of the stack to the top
+	pub fn values(&self) -> &[Value] {
+		&self.values
+	}
+
+	pub fn top(&self) -> Option<&Value> {
+		self.values.last()
+	}
+
+	// if any part of the line fails, the stack is left as it was
+	pub fn run_line(&mut self, runner: &mut Runner, line: &str) -> Result<(), String> {
+		let items = parsing::try_parse_rpn(line)?;
+
+		let mut values = self.values.clone();
+		for item in &items {
+			Self::run_item(runner, &mut values, item)?;
+		}
+		self.values = values;
+
+		Ok(())
+	}
+
+	fn run_item(runner: &mut Runner, values: &mut Vec<Value>, item: &RpnItem) -> Result<(), String> {
+		match item {
+			RpnItem::Binary(op) => {
+				let mut args = pop(values, 2, op.symbol())?;
+				let r = args.pop().unwrap();
+				let l = args.pop().unwrap();
+				values.push(Runner::apply_binary(op, l, r)?);
+			}
+			RpnItem::Call(name) => {
+				let builtin = unwrap!(builtins::find(name), {
+					return Err(format!("Function \"{}\" is undefined", name));
+				});
+
+				// variadic builtins take every value on the stack
+				let count = if builtin.variadic {
+					values.len().max(builtin.params.len())
+				} else {
+					builtin.params.len()
+				};
+
+				let args = pop(values, count, name)?;
+				values.push(runner.apply_builtin(builtin, &args)?);
+			}
+			RpnItem::Operand(expr) => values.push(runner.evaluate(expr)?),
+			RpnItem::Stack(name) => Self::run_stack_command(values, name)?,
+			RpnItem::Unary(op) => {
+				let name = match op {
+					UnaryOp::Negate => "neg",
+					UnaryOp::Not => "not",
+					UnaryOp::Factorial => "!",
+				};
+				let val = pop_one(values, name)?;
+				values.push(Runner::apply_unary(op, val)?);
+			}
+		}
+
+		Ok(())
+	} // run_item
+
+	fn run_stack_command(values: &mut Vec<Value>, name: &str) -> Result<(), String> {
+		match name {
+			"clear" => values.clear(),
+			"drop" => {
+				pop_one(values, name)?;
+			}
+			"dup" => {
+				let val = pop_one(values, name)?;
+				values.push(val.clone());
+				values.push(val);
+			}
+			"roll" => {
+				// n roll moves the nth value down from the top to the top
+				let depth = values.len().saturating_sub(1);
+				let n = match pop_one(values, name)? {
+					Value::Number(n) if n >= 1f64 && n.fract() == 0f64 && n as usize <= depth => {
+						n as usize
+					}
+					val => {
+						return Err(format!(
+							"'roll' expects a position from 1 to {}, but found {}",
+							depth, val
+						))
+					}
+				};
+
+				let val = values.remove(values.len() - n);
+				values.push(val);
+			}
+			"swap" => {
+				let mut args = pop(values, 2, name)?;
+				args.reverse();
+				values.extend(args);
+			}
+			_ => return Err(format!("Unknown stack command \"{}\"", name)),
+		}
+
+		Ok(())
+	} // run_stack_command
+} // Stack
+
+#[cfg(test)]
+mod tests {
+	use crate::rpn::*;
+
+	fn run(lines: &[&str]) -> Result<Vec<Value>, String> {
+		let mut runner = Runner::new();
+		let mut stack = Stack::new();
+		for line in lines {
+			stack.run_line(&mut runner, line)?;
+		}
+
+		Ok(stack.values().to_vec())
+	}
+
+	fn numbers(items: &[f64]) -> Result<Vec<Value>, String> {
+		Ok(items.iter().map(|n| Value::Number(*n)).collect())
+	}
+
+	#[test]
+	fn run_operators() {
+		assert_eq!(run(&["3 4 + 2 *"]), numbers(&[14f64]));
+		assert_eq!(run(&["3 4", "-"]), numbers(&[-1f64]));
+		assert_eq!(run(&["3 -4 *"]), numbers(&[-12f64]));
+		assert_eq!(run(&["2 3 ** 4!"]), numbers(&[8f64, 24f64]));
+		assert_eq!(run(&["5 neg 6 not"]), numbers(&[-5f64, -7f64]));
+		assert_eq!(run(&["(1 + 2) pi floor *"]), numbers(&[9f64]));
+		assert_eq!(run(&["2 7 modpow(2, 3, 5)"]), numbers(&[2f64, 7f64, 3f64]));
+		assert_eq!(run(&["12 18 27 gcd"]), numbers(&[3f64]));
+		assert_eq!(run(&["1 5 2 nCr"]), numbers(&[1f64, 10f64]));
+
+		std::env::set_var("TCALC_TEST_RPN", "5");
+		assert_eq!(run(&["1 \"TCALC_TEST_RPN\" env"]), numbers(&[1f64, 5f64]));
+		assert_eq!(
+			run(&["2026-10-18 1d +"]),
+			Ok(vec![Value::Date(1_792_368_000_000)])
+		);
+	}
+
+	#[test]
+	fn run_stack_commands() {
+		assert_eq!(run(&["1 2 swap"]), numbers(&[2f64, 1f64]));
+		assert_eq!(run(&["1 2 dup"]), numbers(&[1f64, 2f64, 2f64]));
+		assert_eq!(run(&["1 2 drop"]), numbers(&[1f64]));
+		assert_eq!(run(&["1 2 3 3 roll"]), numbers(&[2f64, 3f64, 1f64]));
+		assert_eq!(run(&["1 2 clear 3"]), numbers(&[3f64]));
+	}
+
+	#[test]
+	fn run_errors() {
+		assert_eq!(
+			run(&["1 +"]),
+			Err("'+' expects 2 values on the stack, but found 1".to_string())
+		);
+		assert_eq!(
+			run(&["1 2 3 roll"]),
+			Err("'roll' expects a position from 1 to 2, but found 3".to_string())
+		);
+		assert_eq!(run(&["1 0 /"]), Err("Cannot divide by zero".to_string()));
+		assert_eq!(
+			run(&["1 ="]),
+			Err("Unexpected token 'Equal' (line 1, column 3)".to_string())
+		);
+
+		// a failed line leaves the stack as it was
+		let mut runner = Runner::new();
+		let mut stack = Stack::new();
+		stack.run_line(&mut runner, "1 2").unwrap();
+		assert!(stack.run_line(&mut runner, "3 + + +").is_err());
+		assert_eq!(stack.values(), &numbers(&[1f64, 2f64]).unwrap()[..]);
+	}
+
+	#[test]
+	fn rpn_commands() {
+		assert_eq!(command("vars"), Some(Command::Vars));
+		assert_eq!(
+			command("set mode infix"),
+			Some(Command::Set(Setting::Mode(Mode::Infix)))
+		);
+		assert_eq!(command("clear"), None);
+		assert_eq!(command("3 4 +"), None);
+	}
+}
@@ -1,8 +1,12 @@
 use crate::ast::*;
-use std::collections::HashMap;
+use crate::builtins;
+use crate::datetime;
+use crate::math;
+use crate::value::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::f64::consts::*;
 
-fn too_x_params(call: &Call, count: u8, x: &str) -> Result<f64, String> {
+fn too_x_params(call: &Call, count: u8, x: &str) -> Result<Value, String> {
 	Err(format!(
 		"Call to {}() has to {} parameters; expected {} but found {}.",
 		call.name,
@@ -12,32 +16,139 @@ fn too_x_params(call: &Call, count: u8, x: &str) -> Result<f64, String> {
 	))
 }
 
-fn too_few_params(call: &Call, count: u8) -> Result<f64, String> {
+// integers too large for an f64 are rounded to one where exactness is lost
+// anyway, like in duration arithmetic
+fn inexact(val: Value) -> Value {
+	match val {
+		Value::Integer(n) => Value::Number(n as f64),
+		val => val,
+	}
+}
+
+fn too_few_params(call: &Call, count: u8) -> Result<Value, String> {
 	too_x_params(call, count, "few")
 }
 
-fn too_many_params(call: &Call, count: u8) -> Result<f64, String> {
+fn too_many_params(call: &Call, count: u8) -> Result<Value, String> {
 	too_x_params(call, count, "many")
 }
 
+const PHI: f64 = 1.618_033_988_749_895_f64;
+
+// namespaced constants live under these prefixes, e.g. phys.c
+const NAMESPACES: [&str; 2] = ["math.", "phys."];
+
+// physical constants are CODATA 2018 recommended values
+pub const CONSTANTS: [(&str, f64, &str); 29] = [
+	("e", E, "Euler's number (e)"),
+	("phi", PHI, "Golden ratio (φ)"),
+	("pi", PI, "Archimedes' constant (π)"),
+	("math.e", E, "Euler's number (e)"),
+	("math.egamma", 0.577_215_664_901_532_9, "Euler-Mascheroni constant (γ)"),
+	("math.ln10", LN_10, "Natural log of 10"),
+	("math.ln2", LN_2, "Natural log of 2"),
+	("math.phi", PHI, "Golden ratio (φ)"),
+	("math.pi", PI, "Archimedes' constant (π)"),
+	("math.sqrt2", SQRT_2, "Square root of 2"),
+	("math.tau", TAU, "Full circle constant (τ = 2π)"),
+	("phys.G", 6.674_30e-11, "Newtonian constant of gravitation (m³/kg/s²)"),
+	("phys.N_A", 6.022_140_76e23, "Avogadro constant (1/mol)"),
+	("phys.R", 8.314_462_618, "Molar gas constant (J/mol/K)"),
+	("phys.alpha", 7.297_352_569_3e-3, "Fine-structure constant"),
+	("phys.atm", 101_325f64, "Standard atmosphere (Pa)"),
+	("phys.c", 299_792_458f64, "Speed of light in vacuum (m/s)"),
+	("phys.e_charge", 1.602_176_634e-19, "Elementary charge (C)"),
+	("phys.eps0", 8.854_187_812_8e-12, "Vacuum electric permittivity (F/m)"),
+	("phys.g", 9.806_65, "Standard acceleration of gravity (m/s²)"),
+	("phys.h", 6.626_070_15e-34, "Planck constant (J·s)"),
+	("phys.hbar", 1.054_571_817e-34, "Reduced Planck constant (J·s)"),
+	("phys.k_B", 1.380_649e-23, "Boltzmann constant (J/K)"),
+	("phys.m_e", 9.109_383_701_5e-31, "Electron mass (kg)"),
+	("phys.m_n", 1.674_927_498_04e-27, "Neutron mass (kg)"),
+	("phys.m_p", 1.672_621_923_69e-27, "Proton mass (kg)"),
+	("phys.mu0", 1.256_637_062_12e-6, "Vacuum magnetic permeability (N/A²)"),
+	("phys.sigma", 5.670_374_419e-8, "Stefan-Boltzmann constant (W/m²/K⁴)"),
+	("phys.u", 1.660_539_066_60e-27, "Atomic mass constant (kg)"),
+];
+
+#[derive(Clone)]
 pub struct Runner {
-	scopes: Vec<HashMap<String, f64>>,
+	scopes: Vec<HashMap<String, Value>>,
+	consts: HashSet<String>,
+	// numbered results by number; gaps are left by results which could not be
+	// saved
+	results: BTreeMap<usize, Value>,
+	angle: Angle,
+	// the value of each expression reduced so far, while tracing
+	trace: Option<Vec<Value>>,
 }
 
 impl Runner {
 	pub fn new() -> Runner {
 		let mut sys_scope = HashMap::new();
 
-		sys_scope.insert("e".to_string(), E);
-		sys_scope.insert("phi".to_string(), 1.618_033_988_749_895_f64);
-		sys_scope.insert("pi".to_string(), PI);
+		for (name, value, _) in CONSTANTS {
+			sys_scope.insert(name.to_string(), Value::Number(value));
+		}
 
 		Runner {
 			scopes: vec![sys_scope, HashMap::new()],
+			consts: HashSet::new(),
+			results: BTreeMap::new(),
+			angle: Angle::Radians,
+			trace: None,
+		}
+	}
+
+	pub fn clear(&mut self) {
+		for scope in self.scopes.iter_mut().skip(1) {
+			scope.clear();
+		}
+		self.consts.clear();
+		self.results.clear();
+	}
+
+	pub fn results(&self) -> &BTreeMap<usize, Value> {
+		&self.results
+	}
+
+	// the number of the latest result, or 0 when there are none
+	fn latest_result(&self) -> usize {
+		self.results.keys().next_back().copied().unwrap_or(0)
+	}
+
+	// from the system scope to the innermost user scope
+	pub fn scopes(&self) -> &[HashMap<String, Value>] {
+		&self.scopes
+	}
+
+	pub fn angle(&self) -> Angle {
+		self.angle
+	}
+
+	pub fn set_angle(&mut self, angle: Angle) {
+		self.angle = angle;
+	}
+
+	pub fn is_const(&self, name: &str) -> bool {
+		matches!(name, "now" | "today")
+			|| NAMESPACES.iter().any(|ns| name.starts_with(ns))
+			|| self.scopes[0].contains_key(name)
+			|| self.consts.contains(name)
+	}
+
+	// names with a dot are reserved for namespaced constants, like phys.c
+	fn check_assignable(&self, name: &str) -> Result<(), String> {
+		if self.is_const(name) {
+			Err(format!("Cannot assign to constant \"{}\"", name))
+		} else if name.contains('.') {
+			Err(format!("Cannot assign to \"{}\"; names with a dot are reserved for constants", name))
+		} else {
+			Ok(())
 		}
 	}
 
-	fn scope_get(&self, name: &str) -> Option<&f64> {
+	fn scope_get(&self, name: &str) -> Option<&Value> {
 		for scope in self.scopes.iter().rev() {
 			if let Some(val) = scope.get(name) {
 				return Some(val);
@@ -47,11 +158,11 @@ impl Runner {
 		None
 	}
 
-	fn scope_set(&mut self, name: String, value: f64) {
+	fn scope_set(&mut self, name: String, value: Value) {
 		self.scopes.last_mut().unwrap().insert(name, value);
 	}
 
-	fn scope_unset(&mut self, name: &str) -> Option<f64> {
+	fn scope_unset(&mut self, name: &str) -> Option<Value> {
 		for scope in self.scopes.iter_mut().skip(1).rev() {
 			if let Some(val) = scope.remove(name) {
 				return Some(val);
@@ -61,58 +172,267 @@ impl Runner {
 		None
 	}
 
-	pub fn run_expression(&mut self, expr: &Expression) -> Result<f64, String> {
+	pub fn run_expression(&mut self, expr: &Expression) -> Result<Value, String> {
 		let ans = self._run_expression(expr)?;
 
-		self.scope_set("ans".to_string(), ans);
+		self.scope_set("ans".to_string(), ans.clone());
 
 		Ok(ans)
 	}
 
-	fn _run_expression(&mut self, expr: &Expression) -> Result<f64, String> {
-		match expr {
+	pub fn run_numbered(&mut self, expr: &Expression) -> Result<(usize, Value), String> {
+		let value = self.run_expression(expr)?;
+		let number = self.latest_result() + 1;
+		self.results.insert(number, value.clone());
+
+		Ok((number, value))
+	}
+
+	// like an assignment, without changing ans
+	pub fn define(&mut self, name: &str, value: Value) -> Result<(), String> {
+		self.check_assignable(name)?;
+		self.scope_set(name.to_string(), value);
+		Ok(())
+	}
+
+	// unlike run_expression, ans is not changed
+	pub fn evaluate(&mut self, expr: &Expression) -> Result<Value, String> {
+		self._run_expression(expr)
+	}
+
+	// values are in the order they were reduced, operands before their operator
+	pub fn traced<T, F>(&mut self, f: F) -> (Vec<Value>, Result<T, String>)
+	where
+		F: FnOnce(&mut Runner) -> Result<T, String>,
+	{
+		let outer = self.trace.replace(vec![]);
+		let result = f(self);
+		let trace = std::mem::replace(&mut self.trace, outer).unwrap_or_default();
+
+		(trace, result)
+	}
+
+	fn _run_expression(&mut self, expr: &Expression) -> Result<Value, String> {
+		let value = match expr {
 			Expression::Assignment(a) => self.run_assignment(a),
 			Expression::Binary(b) => self.run_binary(b),
 			Expression::Call(c) => self.run_call(c),
 			Expression::Literal(l) => self.run_literal(l),
+			Expression::Result(r) => self.run_result(r),
 			Expression::Unary(u) => self.run_unary(u),
 			Expression::Variable(v) => self.run_variable(v),
+		}?;
+
+		if let Some(ref mut trace) = self.trace {
+			trace.push(value.clone());
 		}
+
+		Ok(value)
 	}
 
 	pub fn run_statement(&mut self, stmt: &Statement) -> Result<(), String> {
 		match stmt {
+			Statement::Const(assign) => self.run_const(assign),
 			Statement::DeleteVar(var) => self.run_delete_var(var),
+			Statement::SetResult(number, expr) => self.run_set_result(*number, expr),
 		}
 	}
 
-	fn run_literal(&self, lit: &Literal) -> Result<f64, String> {
+	fn run_literal(&self, lit: &Literal) -> Result<Value, String> {
 		match lit {
-			Literal::Number(n) => Ok(*n),
+			Literal::Date(millis) => Ok(Value::Date(*millis)),
+			Literal::Duration(millis) => Ok(Value::Duration(*millis)),
+			Literal::Integer(n) => Ok(Value::Integer(*n)),
+			Literal::Number(n) => Ok(Value::Number(*n)),
+			Literal::String(str) => Ok(Value::String(str.clone())),
 		}
 	} // run_literal
 
-	fn run_variable(&self, var: &Variable) -> Result<f64, String> {
+	fn run_variable(&self, var: &Variable) -> Result<Value, String> {
 		match self.scope_get(&var.name) {
-			Some(val) => Ok(*val),
-			None => Err(format!("Variable \"{}\" is undefined", var.name)),
+			Some(val) => Ok(val.clone()),
+			None => match var.name.as_str() {
+				"now" => Ok(Value::Date(datetime::now())),
+				"today" => Ok(Value::Date(datetime::today())),
+				_ => Err(format!("Variable \"{}\" is undefined", var.name)),
+			},
 		}
 	} // run_variable
 
-	fn run_unary(&mut self, un: &Unary) -> Result<f64, String> {
-		let r = self._run_expression(&*un.right)?;
+	fn run_result(&mut self, r: &ResultRef) -> Result<Value, String> {
+		let (number, name) = match r {
+			ResultRef::Latest => (self.latest_result() as i64, "$$".to_string()),
+			ResultRef::Number(n) => (*n as i64, format!("${}", n)),
+			ResultRef::Offset(expr) => {
+				let val = self._run_expression(expr)?;
+				let offset = match val.exact_integer() {
+					Some(n) if n < 0 => n,
+					_ => {
+						return Err(format!(
+							"Result offsets must be negative integers, e.g. ans[-1], but found {}",
+							val
+						))
+					}
+				};
+
+				let number = self.latest_result() as i128 + 1 + offset;
+				(number.max(0) as i64, format!("ans[{}]", offset))
+			}
+		};
 
-		match un.op {
-			UnaryOp::Negate => Ok(-r),
-			UnaryOp::Not => Ok(!(r as i64) as f64),
+		match self.results.get(&(number.max(0) as usize)) {
+			Some(val) => Ok(val.clone()),
+			_ => Err(format!("Result {} is undefined", name)),
 		}
-	} // run_unary
+	} // run_result
 
-	fn run_binary(&mut self, bin: &Binary) -> Result<f64, String> {
-		let l = self._run_expression(&*bin.left)?;
-		let r = self._run_expression(&*bin.right)?;
+	fn run_unary(&mut self, un: &Unary) -> Result<Value, String> {
+		let r = self._run_expression(&un.right)?;
+		Self::apply_unary(&un.op, r)
+	}
 
-		match bin.op {
+	pub fn apply_unary(op: &UnaryOp, val: Value) -> Result<Value, String> {
+		let exact = val.exact_integer().and_then(|n| match op {
+			UnaryOp::Factorial => None,
+			UnaryOp::Negate => n.checked_neg(),
+			UnaryOp::Not => Some(!n),
+		});
+		if let Some(n) = exact {
+			return Ok(Value::integer(n));
+		}
+
+		let r = match val {
+			Value::Integer(n) => n as f64,
+			Value::Number(r) => r,
+			Value::Duration(millis) if matches!(op, UnaryOp::Negate) => {
+				return Ok(Value::Duration(-millis));
+			}
+			val => {
+				return Err(format!(
+					"Cannot apply operator '{}' to a {}",
+					op.symbol(),
+					val.type_name()
+				))
+			}
+		};
+
+		match op {
+			UnaryOp::Factorial => {
+				if r.fract() != 0f64 || r < 0f64 {
+					return Err(format!(
+						"Factorial expects a non-negative integer, but found {}",
+						r
+					));
+				}
+				Ok(Value::Number(math::factorial(r as u64)))
+			}
+			UnaryOp::Negate => Ok(Value::Number(-r)),
+			UnaryOp::Not => Ok(Value::Number(!(r as i64) as f64)),
+		}
+	} // apply_unary
+
+	fn run_binary(&mut self, bin: &Binary) -> Result<Value, String> {
+		let l = self._run_expression(&bin.left)?;
+		let r = self._run_expression(&bin.right)?;
+		Self::apply_binary(&bin.op, l, r)
+	}
+
+	pub fn apply_binary(op: &BinaryOp, l: Value, r: Value) -> Result<Value, String> {
+		if let (Some(l), Some(r)) = (l.exact_integer(), r.exact_integer()) {
+			if let Some(val) = Self::run_integer_binary(op, l, r)? {
+				return Ok(val);
+			}
+		}
+
+		match (l.number(), r.number()) {
+			(Some(l), Some(r)) => Self::run_number_binary(op, l, r).map(Value::Number),
+			_ => Self::run_time_binary(op, inexact(l), inexact(r)),
+		}
+	}
+
+	// integers stay exact, unless the result is not an integer, like 1 / 2, or
+	// overflows 128 bits, when None is returned so it is computed as an f64
+	fn run_integer_binary(op: &BinaryOp, l: i128, r: i128) -> Result<Option<Value>, String> {
+		if r == 0 && matches!(op, BinaryOp::Divide | BinaryOp::Modulo) {
+			return Err("Cannot divide by zero".to_string());
+		}
+
+		let n = match op {
+			BinaryOp::BitAnd => Some(l & r),
+			BinaryOp::BitOr => Some(l | r),
+			BinaryOp::BitXor => Some(l ^ r),
+			BinaryOp::LeftShift => u32::try_from(r)
+				.ok()
+				.filter(|r| *r < 127)
+				.and_then(|r| l.checked_mul(1 << r)),
+			BinaryOp::RightShift => u32::try_from(r).ok().map(|r| l >> r.min(127)),
+			BinaryOp::Plus => l.checked_add(r),
+			BinaryOp::Minus => l.checked_sub(r),
+			BinaryOp::Multiply => l.checked_mul(r),
+			BinaryOp::Divide => l.checked_rem(r).filter(|m| *m == 0).and_then(|_| l.checked_div(r)),
+			BinaryOp::Modulo => l.checked_rem(r),
+			BinaryOp::Exponent => u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+		};
+
+		Ok(n.map(Value::integer))
+	} // run_integer_binary
+
+	fn run_time_binary(op: &BinaryOp, l: Value, r: Value) -> Result<Value, String> {
+		let out_of_range = || format!("Result of '{}' is out of range", op.symbol());
+
+		match (op, &l, &r) {
+			(BinaryOp::Minus, Value::Date(l), Value::Date(r)) => {
+				Ok(Value::Duration(l - r))
+			}
+			(BinaryOp::Plus, Value::Date(d), Value::Duration(r))
+			| (BinaryOp::Plus, Value::Duration(r), Value::Date(d)) => {
+				Value::date(d.checked_add(*r).ok_or_else(out_of_range)?)
+			}
+			(BinaryOp::Minus, Value::Date(l), Value::Duration(r)) => {
+				Value::date(l.checked_sub(*r).ok_or_else(out_of_range)?)
+			}
+			(BinaryOp::Plus, Value::Duration(l), Value::Duration(r)) => {
+				Value::duration(*l as f64 + *r as f64)
+			}
+			(BinaryOp::Minus, Value::Duration(l), Value::Duration(r)) => {
+				Value::duration(*l as f64 - *r as f64)
+			}
+			(BinaryOp::Multiply, Value::Duration(l), Value::Number(r))
+			| (BinaryOp::Multiply, Value::Number(r), Value::Duration(l)) => {
+				Value::duration(*l as f64 * r)
+			}
+			(BinaryOp::Divide, Value::Duration(l), Value::Number(r)) => {
+				if *r == 0f64 {
+					Err("Cannot divide by zero".to_string())
+				} else {
+					Value::duration(*l as f64 / r)
+				}
+			}
+			(BinaryOp::Divide, Value::Duration(l), Value::Duration(r)) => {
+				if *r == 0 {
+					Err("Cannot divide by zero".to_string())
+				} else {
+					Ok(Value::Number(*l as f64 / *r as f64))
+				}
+			}
+			(BinaryOp::Modulo, Value::Duration(l), Value::Duration(r)) => {
+				if *r == 0 {
+					Err("Cannot divide by zero".to_string())
+				} else {
+					Ok(Value::Duration(l % r))
+				}
+			}
+			_ => Err(format!(
+				"Cannot apply operator '{}' to a {} and a {}",
+				op.symbol(),
+				l.type_name(),
+				r.type_name()
+			)),
+		}
+	} // run_time_binary
+
+	fn run_number_binary(op: &BinaryOp, l: f64, r: f64) -> Result<f64, String> {
+		match op {
 			BinaryOp::BitAnd => Ok(((l as i64) & (r as i64)) as f64),
 			BinaryOp::BitOr => Ok(((l as i64) | (r as i64)) as f64),
 			BinaryOp::BitXor => Ok(((l as i64) ^ (r as i64)) as f64),
@@ -137,55 +457,120 @@ impl Runner {
 			}
 			BinaryOp::Exponent => Ok(l.powf(r)),
 		}
-	} // run_binary
+	} // run_number_binary
+
+	fn run_assignment(&mut self, assign: &Assignment) -> Result<Value, String> {
+		self.check_assignable(&assign.var.name)?;
 
-	fn run_assignment(&mut self, assign: &Assignment) -> Result<f64, String> {
-		let r = self._run_expression(&*assign.right)?;
-		self.scope_set(assign.var.name.clone(), r);
+		let r = self._run_expression(&assign.right)?;
+		self.scope_set(assign.var.name.clone(), r.clone());
 		Ok(r)
 	}
 
-	fn run_call(&mut self, call: &Call) -> Result<f64, String> {
-		match call.name.as_str() {
-			"abs" => match call.params.len() {
-				0 => too_few_params(call, 1),
-				1 => {
-					let val = self._run_expression(&call.params[0])?;
-					Ok(val.abs())
-				}
-				_ => too_many_params(call, 1),
-			},
-			"ceil" => match call.params.len() {
-				0 => too_few_params(call, 1),
-				1 => {
-					let val = self._run_expression(&call.params[0])?;
-					Ok(val.ceil())
-				}
-				_ => too_many_params(call, 1),
-			},
-			"floor" => match call.params.len() {
-				0 => too_few_params(call, 1),
-				1 => {
-					let val = self._run_expression(&call.params[0])?;
-					Ok(val.floor())
+	fn run_params(&mut self, call: &Call) -> Result<Vec<Value>, String> {
+		call.params
+			.iter()
+			.map(|p| self._run_expression(p))
+			.collect()
+	}
+
+	fn run_call(&mut self, call: &Call) -> Result<Value, String> {
+		let builtin = unwrap!(builtins::find(&call.name), {
+			return Err(format!("Function \"{}\" is undefined", call.name));
+		});
+
+		let count = builtin.params.len() as u8;
+		if call.params.len() < builtin.params.len() {
+			return too_few_params(call, count);
+		} else if call.params.len() > builtin.params.len() && !builtin.variadic {
+			return too_many_params(call, count);
+		}
+
+		let args = self.run_params(call)?;
+		self.call_builtin(builtin, call, args)
+	}
+
+	// builtins work in radians, so angles in degrees are converted around them
+	fn call_builtin(
+		&self,
+		builtin: &builtins::Builtin,
+		call: &Call,
+		mut args: Vec<Value>,
+	) -> Result<Value, String> {
+		if self.angle == Angle::Radians {
+			return (builtin.run)(call, &args);
+		}
+
+		if builtin.angles == builtins::Angles::Params {
+			for arg in args.iter_mut() {
+				if let Some(n) = arg.number() {
+					*arg = Value::Number(n.to_radians());
 				}
-				_ => too_many_params(call, 1),
-			},
-			"round" => match call.params.len() {
-				0 => too_few_params(call, 1),
-				1 => {
-					let val = self._run_expression(&call.params[0])?;
-					Ok(val.round())
+			}
+		}
+
+		match (builtin.run)(call, &args)? {
+			Value::Number(n) if builtin.angles == builtins::Angles::Result => {
+				Ok(Value::Number(n.to_degrees()))
+			}
+			val => Ok(val),
+		}
+	} // call_builtin
+
+	// e.g. with values popped off the stack in RPN mode
+	pub fn apply_builtin(&self, builtin: &builtins::Builtin, args: &[Value]) -> Result<Value, String> {
+		let call = Call {
+			name: builtin.name.to_string(),
+			params: vec![],
+		};
+
+		self.call_builtin(builtin, &call, args.to_vec())
+	}
+
+	fn run_const(&mut self, assign: &Assignment) -> Result<(), String> {
+		let name = &assign.var.name;
+		if self.is_const(name) {
+			// redeclaring a user constant with the same value is harmless, and
+			// lets a saved session be loaded over the init script
+			if self.consts.contains(name) {
+				let r = self._run_expression(&assign.right)?;
+				if self.scope_get(name) == Some(&r) {
+					return Ok(());
 				}
-				_ => too_many_params(call, 1),
-			},
-			// rand
-			_ => Err(format!("Function \"{}\" is undefined", call.name)),
+			}
+
+			return Err(format!("Constant \"{}\" is already defined", name));
 		}
+
+		// ans is overwritten by every expression, so it can never be constant,
+		// and names with a dot are reserved for builtin ones
+		if name == "ans" || name.contains('.') {
+			return Err(format!("Cannot declare \"{}\" as a constant", name));
+		}
+
+		let r = self._run_expression(&assign.right)?;
+		self.scope_set(name.clone(), r);
+		self.consts.insert(name.clone());
+
+		Ok(())
+	}
+
+	// results may only be added after the latest, so existing results never change
+	fn run_set_result(&mut self, number: usize, expr: &Expression) -> Result<(), String> {
+		if number <= self.latest_result() {
+			return Err(format!("Result ${} is already defined", number));
+		}
+
+		let value = self._run_expression(expr)?;
+		self.results.insert(number, value);
+
+		Ok(())
 	}
 
 	fn run_delete_var(&mut self, var: &Variable) -> Result<(), String> {
-		if self.scope_unset(&var.name) == None {
+		if self.is_const(&var.name) {
+			Err(format!("Cannot delete constant \"{}\"", var.name))
+		} else if self.scope_unset(&var.name).is_none() {
 			Err(format!("Variable \"{}\" is undefined", var.name))
 		} else {
 			Ok(())
@@ -197,8 +582,9 @@ impl Runner {
 mod tests {
 	use crate::parsing::*;
 	use crate::running::*;
+	use std::env;
 
-	fn solve(input: &str) -> f64 {
+	fn run(input: &str) -> Result<Value, String> {
 		let ast = unwrap!(parse(input), {
 			panic!("Expected Ast for input \"{}\", but found None", input);
 		});
@@ -211,12 +597,49 @@ mod tests {
 			),
 		};
 
-		match Runner::new().run_expression(&expr) {
+		Runner::new().run_expression(&expr)
+	} // run
+
+	fn run_session(inputs: &[&str]) -> Result<Value, String> {
+		let mut runner = Runner::new();
+		let mut last = Value::Number(0f64);
+
+		for input in inputs {
+			match parse(input) {
+				Some(Ast::Expression(expr)) => last = runner.run_expression(&expr)?,
+				Some(Ast::Statement(stmt)) => runner.run_statement(&stmt)?,
+				ast => panic!("Unexpected Ast for input \"{}\": {:?}", input, ast),
+			}
+		}
+
+		Ok(last)
+	} // run_session
+
+	fn solve_value(input: &str) -> Value {
+		match run(input) {
 			Ok(v) => v,
 			Err(msg) => panic!("Error for input \"{}\": {}", input, msg),
 		}
+	} // solve_value
+
+	fn solve(input: &str) -> f64 {
+		match solve_value(input) {
+			Value::Number(n) => n,
+			v => panic!("Expected number for input \"{}\", but found {:?}", input, v),
+		}
 	} // solve
 
+	fn fail(input: &str) -> String {
+		match run(input) {
+			Ok(v) => panic!("Expected error for input \"{}\", but found {:?}", input, v),
+			Err(msg) => msg,
+		}
+	} // fail
+
+	fn list(items: &[f64]) -> Value {
+		Value::List(items.iter().map(|n| Value::Number(*n)).collect())
+	}
+
 	#[test]
 	fn solve_literal() {
 		assert_eq!(solve("123"), 123f64);
@@ -278,4 +701,320 @@ mod tests {
 
 		assert_eq!(solve("a = abs(ceil(floor(-1.234 * 10) / 10))"), 1f64);
 	}
+
+	#[test]
+	fn solve_number_theory() {
+		assert_eq!(solve("gcd(12, 18, 27)"), 3f64);
+		assert_eq!(solve("lcm(4, 6, 10)"), 60f64);
+		assert_eq!(solve("isprime(97)"), 1f64);
+		assert_eq!(solve("isprime(91)"), 0f64);
+		assert_eq!(
+			solve_value("factor(360)"),
+			list(&[2f64, 2f64, 2f64, 3f64, 3f64, 5f64])
+		);
+		assert_eq!(solve_value("factor(1)"), list(&[]));
+		assert_eq!(solve("nextprime(89)"), 97f64);
+		assert_eq!(solve("totient(36)"), 12f64);
+		assert_eq!(solve("modpow(4, 13, 497)"), 445f64);
+		assert_eq!(solve("modinv(3, 11)"), 4f64);
+		assert_eq!(solve_value("egcd(240, 46)"), list(&[2f64, -9f64, 47f64]));
+
+		// integers from 2^53 on are exact, rather than rounded f64s
+		assert_eq!(solve("isprime(2**61 - 1)"), 1f64);
+		assert_eq!(solve("isprime(9007199254740993)"), 0f64);
+		assert_eq!(solve_value("nextprime(2**62)"), Value::Integer(4_611_686_018_427_388_039));
+		assert_eq!(
+			solve_value("factor(2**62 - 1)"),
+			Value::List(vec![
+				Value::Number(3f64),
+				Value::Number(715_827_883f64),
+				Value::Number(2_147_483_647f64),
+			])
+		);
+	}
+
+	#[test]
+	fn solve_integers() {
+		assert_eq!(solve_value("2**53 + 1"), Value::Integer(9_007_199_254_740_993));
+		assert_eq!(solve_value("2**53 + 1 - 2**53"), Value::Number(1f64));
+		assert_eq!(solve_value("0x20000000000001 * 3"), Value::Integer(27_021_597_764_222_979));
+		assert_eq!(solve_value("-(2**100) / 2**99"), Value::Number(-2f64));
+		assert_eq!(solve_value("(2**64 + 1) % 10"), Value::Number(7f64));
+		assert_eq!(solve_value("1 << 100 >> 98"), Value::Number(4f64));
+		assert_eq!(solve("(2**53 + 2) / 4"), 2_251_799_813_685_248.5);
+		assert_eq!(solve("2**127 * 2"), 2f64.powi(128));
+		assert_eq!(solve("2**-1"), 0.5);
+		assert_eq!(fail("2**60 % 0"), "Cannot divide by zero");
+	}
+
+	#[test]
+	fn solve_combinatorics() {
+		assert_eq!(solve("5!"), 120f64);
+		assert_eq!(solve("3!!"), 720f64);
+		assert_eq!(solve("-3!"), -6f64);
+		assert_eq!(solve("2**3!"), 64f64);
+		assert_eq!(solve("!3!"), -7f64);
+		assert_eq!(solve("fact(6)"), 720f64);
+		assert_eq!(solve("nCr(52, 5)"), 2_598_960f64);
+		assert_eq!(solve("choose(5, 2)"), 10f64);
+		assert_eq!(solve("nPr(5, 2)"), 20f64);
+		assert_eq!(solve("gamma(5)"), 24f64);
+		assert_eq!(solve("gamma(1000)"), f64::INFINITY);
+		assert_eq!(solve("round(lgamma(10) * 1000)"), 12_802f64);
+		assert_eq!(solve("round(1 / beta(2, 3))"), 12f64);
+
+		assert_eq!(
+			fail("2.5!"),
+			"Factorial expects a non-negative integer, but found 2.5"
+		);
+		assert_eq!(
+			fail("nCr(-1, 2)"),
+			"Call to nCr() expects non-negative integer parameters, but found -1"
+		);
+		assert_eq!(fail("gamma(-2)"), "Call to gamma() is undefined for -2");
+		assert_eq!(fail("beta(-1, 2)"), "Call to beta() is undefined for -1");
+		assert_eq!(fail("beta(2, 0)"), "Call to beta() is undefined for 0");
+		assert_eq!(fail("beta(-1.5, 0.5)"), "Call to beta() is undefined for -1");
+	}
+
+	#[test]
+	fn solve_trig() {
+		assert_eq!(solve("sin(pi / 2)"), 1f64);
+		assert_eq!(solve("cos(pi)"), -1f64);
+		assert_eq!(solve("atan2(1, 1)"), FRAC_PI_4);
+		assert_eq!(fail("asin(2)"), "Call to asin() is undefined for 2");
+
+		let mut runner = Runner::new();
+		runner.set_angle(Angle::Degrees);
+		let mut run = |input: &str| match parse(input) {
+			Some(Ast::Expression(expr)) => runner.run_expression(&expr),
+			ast => panic!("Unexpected Ast for input \"{}\": {:?}", input, ast),
+		};
+		assert_eq!(run("sin(90)"), Ok(Value::Number(1f64)));
+		assert_eq!(run("acos(-1)"), Ok(Value::Number(180f64)));
+		assert_eq!(run("atan2(1, 1)"), Ok(Value::Number(45f64)));
+	}
+
+	#[test]
+	fn solve_env() {
+		env::set_var("TCALC_TEST_RATE", "0.07");
+		env::set_var("TCALC_TEST_NAME", "tcalc");
+
+		assert_eq!(solve("env(\"TCALC_TEST_RATE\")"), 0.07);
+		assert_eq!(solve_value("\"a \\\"b\\\"\""), Value::String("a \"b\"".to_string()));
+		assert_eq!(
+			fail("env(\"TCALC_TEST_UNSET\")"),
+			"Environment variable \"TCALC_TEST_UNSET\" is not set"
+		);
+		assert_eq!(
+			fail("env(\"TCALC_TEST_NAME\")"),
+			"Environment variable \"TCALC_TEST_NAME\" is not a number: \"tcalc\""
+		);
+		assert_eq!(
+			fail("env(\"TCALC_TEST_UNSET\", 12)"),
+			"Call to env() has to many parameters; expected 1 but found 2."
+		);
+		assert_eq!(
+			fail("env(5)"),
+			"Call to env() expects a string parameter, but found a number"
+		);
+		assert_eq!(
+			fail("\"a\" + 1"),
+			"Cannot apply operator '+' to a string and a number"
+		);
+	}
+
+	#[test]
+	fn solve_dates() {
+		let date = |str| Value::Date(datetime::parse_date(str).unwrap());
+
+		assert_eq!(solve_value("2026-10-18"), date("2026-10-18"));
+		assert_eq!(solve_value("2026-10-18 + 3d 4h"), date("2026-10-21T04:00"));
+		assert_eq!(solve_value("3d 4h + 2026-10-18"), date("2026-10-21T04:00"));
+		assert_eq!(solve_value("2026-10-18 - 90min"), date("2026-10-17T22:30"));
+		assert_eq!(
+			solve_value("2026-10-18T12:00Z - 2026-10-18"),
+			Value::Duration(12 * datetime::MILLIS_PER_HOUR)
+		);
+		assert_eq!(solve("unix(2026-10-18)"), 1_792_281_600f64);
+		assert_eq!(solve_value("fromunix(1_792_281_600)"), date("2026-10-18"));
+		assert_eq!(solve_value("fromunix(0.5)"), Value::Date(500));
+		assert_eq!(solve_value("today - today"), Value::Duration(0));
+
+		assert_eq!(
+			fail("2026-10-18 + 2026-10-18"),
+			"Cannot apply operator '+' to a date and a date"
+		);
+		assert_eq!(
+			fail("unix(5)"),
+			"Call to unix() expects a date parameter, but found a number"
+		);
+		assert_eq!(fail("2026-10-18 + 3000000d"), "Date is out of range");
+		assert_eq!(fail("0000-01-01 - 1ms"), "Date is out of range");
+		assert_eq!(fail("fromunix(-2**40)"), "Date is out of range");
+	}
+
+	#[test]
+	fn solve_durations() {
+		assert_eq!(solve_value("1h - 90min"), Value::Duration(-1_800_000));
+		assert_eq!(solve_value("-(1h 30min)"), Value::Duration(-5_400_000));
+		assert_eq!(solve_value("2 * 1.5h"), Value::Duration(10_800_000));
+		assert_eq!(solve_value("1d / 4"), Value::Duration(21_600_000));
+		assert_eq!(solve("1d / 1h"), 24f64);
+		assert_eq!(solve_value("1d % 5h"), Value::Duration(14_400_000));
+		assert_eq!(fail("1d / 0"), "Cannot divide by zero");
+		assert_eq!(
+			fail("1d * 1h"),
+			"Cannot apply operator '*' to a duration and a duration"
+		);
+	}
+
+	#[test]
+	fn solve_constants() {
+		assert_eq!(
+			run_session(&["const rate = 0.25", "rate * 100"]),
+			Ok(Value::Number(25f64))
+		);
+		assert_eq!(
+			run_session(&["pi = 3"]),
+			Err("Cannot assign to constant \"pi\"".to_string())
+		);
+		assert_eq!(
+			run_session(&["e++"]),
+			Err("Cannot assign to constant \"e\"".to_string())
+		);
+		assert_eq!(
+			run_session(&["now = 0"]),
+			Err("Cannot assign to constant \"now\"".to_string())
+		);
+		assert_eq!(
+			run_session(&["const a = 1", "a += 2"]),
+			Err("Cannot assign to constant \"a\"".to_string())
+		);
+		assert_eq!(
+			run_session(&["const a = 1", "delete a"]),
+			Err("Cannot delete constant \"a\"".to_string())
+		);
+		assert_eq!(
+			run_session(&["const a = 1", "const a = 2"]),
+			Err("Constant \"a\" is already defined".to_string())
+		);
+		assert_eq!(run_session(&["const a = 2", "const a = 1 + 1", "a"]), Ok(Value::Number(2f64)));
+		assert_eq!(
+			run_session(&["const pi = 3"]),
+			Err("Constant \"pi\" is already defined".to_string())
+		);
+		assert_eq!(
+			run_session(&["const ans = 3"]),
+			Err("Cannot declare \"ans\" as a constant".to_string())
+		);
+		assert_eq!(
+			run_session(&["a = 1", "const a = 2", "a"]),
+			Ok(Value::Number(2f64))
+		);
+	}
+
+	#[test]
+	fn solve_namespaced_constants() {
+		assert_eq!(solve("phys.c"), 299_792_458f64);
+		assert_eq!(solve("math.tau / 2"), PI);
+		assert_eq!(solve("phys.k_B * phys.N_A"), 8.314_462_618_153_24);
+		assert_eq!(fail("phys.foo"), "Variable \"phys.foo\" is undefined");
+		assert_eq!(
+			run_session(&["math.x = 2"]),
+			Err("Cannot assign to constant \"math.x\"".to_string())
+		);
+		assert_eq!(
+			run_session(&["my.rate = 2"]),
+			Err("Cannot assign to \"my.rate\"; names with a dot are reserved for constants".to_string())
+		);
+		assert_eq!(
+			run_session(&["const my.rate = 2"]),
+			Err("Cannot declare \"my.rate\" as a constant".to_string())
+		);
+	}
+
+	#[test]
+	fn clear_user_scope() {
+		let mut runner = Runner::new();
+		for input in ["a = 1", "const b = 2"] {
+			if let Some(Ast::Expression(expr)) = parse(input) {
+				runner.run_expression(&expr).unwrap();
+			} else if let Some(Ast::Statement(stmt)) = parse(input) {
+				runner.run_statement(&stmt).unwrap();
+			}
+		}
+
+		assert_eq!(runner.scopes()[1].len(), 3);
+		assert!(runner.is_const("b"));
+
+		runner.clear();
+		assert!(runner.scopes()[1].is_empty());
+		assert!(!runner.is_const("b"));
+		assert!(runner.is_const("pi"));
+	}
+
+	#[test]
+	fn numbered_results() {
+		let mut runner = Runner::new();
+		let mut run = |input: &str| match parse(input) {
+			Some(Ast::Expression(expr)) => runner.run_numbered(&expr).map(|(_, v)| v),
+			Some(Ast::Statement(stmt)) => runner.run_statement(&stmt).map(|_| Value::Number(0f64)),
+			ast => panic!("Unexpected Ast for input \"{}\": {:?}", input, ast),
+		};
+
+		assert_eq!(run("$$"), Err("Result $$ is undefined".to_string()));
+		for input in ["10", "20", "30"] {
+			run(input).unwrap();
+		}
+		assert_eq!(run("$1 + $$"), Ok(Value::Number(40f64)));
+		assert_eq!(run("ans[-1]"), Ok(Value::Number(40f64)));
+		assert_eq!(run("ans[-2] - ans[-4]"), Ok(Value::Number(20f64)));
+		assert_eq!(run("$9"), Err("Result $9 is undefined".to_string()));
+		assert_eq!(run("ans[-9]"), Err("Result ans[-9] is undefined".to_string()));
+		assert_eq!(
+			run("ans[1]"),
+			Err("Result offsets must be negative integers, e.g. ans[-1], but found 1".to_string())
+		);
+		assert_eq!(run("$2 = 5"), Err("Result $2 is already defined".to_string()));
+		assert!(run("$9 = 5").is_ok());
+		assert_eq!(run("$9"), Ok(Value::Number(5f64)));
+		assert_eq!(run("$8"), Err("Result $8 is undefined".to_string()));
+		assert!(run("$100000000000 = 6").is_ok());
+		assert_eq!(run("$100000000000 + 1"), Ok(Value::Number(7f64)));
+		assert_eq!(run("$100000000001"), Ok(Value::Number(7f64)));
+	}
+
+	#[test]
+	fn solve_number_theory_errors() {
+		assert_eq!(
+			fail("gcd(4, 2.5)"),
+			"Call to gcd() expects integer parameters, but found 2.5"
+		);
+		assert_eq!(
+			fail("isprime(2.5 * 2**60)"),
+			"Call to isprime() has a parameter too large to be represented exactly: 2882303761517117400"
+		);
+		assert_eq!(
+			fail("isprime(2**63)"),
+			"Call to isprime() expects integers of up to 64 bits, but found 9223372036854775808"
+		);
+		assert_eq!(
+			fail("nextprime(2**63 - 25)"),
+			"Result of nextprime() is too large for a 64-bit integer"
+		);
+		assert_eq!(
+			fail("factor(0)"),
+			"Call to factor() expects a positive integer, but found 0"
+		);
+		assert_eq!(fail("modinv(6, 9)"), "6 has no inverse modulo 9");
+		assert_eq!(
+			fail("modpow(2, 3, 0)"),
+			"Call to modpow() has a modulus of zero"
+		);
+		assert_eq!(
+			fail("factor(12) + 1"),
+			"Cannot apply operator '+' to a list and a number"
+		);
+	}
 } // mod tests
@@ -1,6 +1,32 @@
 use crate::buffered_iterator::*;
+use crate::datetime;
+use std::fmt;
 use std::str::Chars;
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxError {
+	pub message: String,
+	// line and column, from 1
+	pub position: Option<(u32, u32)>,
+}
+
+impl SyntaxError {
+	pub fn new(message: String, position: Option<(u32, u32)>) -> SyntaxError {
+		SyntaxError { message, position }
+	}
+}
+
+impl fmt::Display for SyntaxError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.position {
+			Some((line, column)) => {
+				write!(f, "{} (line {}, column {})", self.message, line, column)
+			}
+			None => write!(f, "{}", self.message),
+		}
+	}
+}
+
 #[derive(Debug, PartialEq)]
 pub enum TokenType {
 	Ampersand,
@@ -9,6 +35,8 @@ pub enum TokenType {
 	Caret,
 	CaretEqual,
 	Comma,
+	Date { str: String },
+	Duration { str: String, unit: String },
 	Equal,
 	EqualRightAngleBracket,
 	ForwardSlash,
@@ -16,6 +44,7 @@ pub enum TokenType {
 	Identifier { str: String },
 	LeftAngleBracketX2,
 	LeftAngleBracketX2Equal,
+	LeftBracket,
 	LeftParen,
 	Minus,
 	MinusEqual,
@@ -29,13 +58,17 @@ pub enum TokenType {
 	Plus,
 	PlusEqual,
 	PlusX2,
+	ResultRef { str: String },
 	RightAngleBracketX2,
 	RightAngleBracketX2Equal,
+	RightBracket,
 	RightParen,
+	Semicolon,
 	Star,
 	StarEqual,
 	StarX2,
 	StarX2Equal,
+	String { str: String },
 }
 
 pub struct Token {
@@ -43,12 +76,20 @@ pub struct Token {
 	pub line: u32,
 	pub column: u32,
 	pub length: u32,
+	// in bytes, from the start of the input
+	pub offset: usize,
 }
 
 pub struct Scanner<'a> {
 	chars: BufferedIterator<char, Chars<'a>>,
 	line: u32,
 	column: u32,
+	offset: usize,
+	// where the token being scanned starts
+	start: usize,
+	errors: Vec<SyntaxError>,
+	depth: u32,
+	continues: bool,
 }
 
 impl<'a> Iterator for Scanner<'a> {
@@ -68,37 +109,45 @@ impl<'a> Scanner<'a> {
 			chars: buf,
 			line: 1,
 			column: 1,
+			offset: 0,
+			start: 0,
+			errors: vec![],
+			depth: 0,
+			continues: false,
 		}
 	}
 
-	fn expected_char(&self, expected: char, found: char) {
-		println!(
-			"Expected '{}' but found '{}' instead (line {}, column {})",
-			expected, found, self.line, self.column
-		);
+	pub fn take_errors(&mut self) -> Vec<SyntaxError> {
+		std::mem::take(&mut self.errors)
 	}
 
-	fn unexpected_char(&self, found: char) {
-		println!(
-			"Unexpected character '{}' (line {}, column {})",
-			found, self.line, self.column
-		);
+	fn error(&mut self, message: String) {
+		self.errors
+			.push(SyntaxError::new(message, Some((self.line, self.column))));
 	}
 
-	fn unexpected_end_of_input(&self) {
-		println!(
-			"Unexpected end of input (line {}, column {})",
-			self.line, self.column
-		);
+	fn expected_char(&mut self, expected: char, found: char) {
+		self.error(format!("Expected '{}' but found '{}' instead", expected, found));
+	}
+
+	fn unexpected_char(&mut self, found: char) {
+		self.error(format!("Unexpected character '{}'", found));
+	}
+
+	fn unexpected_end_of_input(&mut self) {
+		self.error("Unexpected end of input".to_string());
 	}
 
 	fn get_char(&mut self) -> Option<char> {
+		let c = self.chars.pop()?;
 		self.column += 1;
-		self.chars.pop()
+		self.offset += c.len_utf8();
+		Some(c)
 	}
 
 	fn put_char(&mut self, c: char) {
 		self.column -= 1;
+		self.offset -= c.len_utf8();
 		self.chars.push(c)
 	}
 
@@ -147,6 +196,7 @@ impl<'a> Scanner<'a> {
 			line: self.line,
 			column: self.column - length,
 			length,
+			offset: self.start,
 		};
 
 		trace!(
@@ -160,22 +210,73 @@ impl<'a> Scanner<'a> {
 		Some(t)
 	}
 
+	// new lines inside parens, or after a token which needs a right-hand side,
+	// continue the current line rather than ending it
 	fn next(&mut self) -> Option<Token> {
+		loop {
+			let t = self.scan_token()?;
+
+			match t.token_type {
+				TokenType::LeftBracket | TokenType::LeftParen => self.depth += 1,
+				TokenType::RightBracket | TokenType::RightParen => {
+					self.depth = self.depth.saturating_sub(1)
+				}
+				TokenType::NewLine if self.depth > 0 || self.continues => continue,
+				_ => {}
+			}
+
+			self.continues = !matches!(
+				t.token_type,
+				TokenType::Bang
+					| TokenType::Date { .. }
+					| TokenType::Duration { .. }
+					| TokenType::Identifier { .. }
+					| TokenType::MinusX2
+					| TokenType::NewLine
+					| TokenType::Number { .. }
+					| TokenType::PlusX2
+					| TokenType::ResultRef { .. }
+					| TokenType::RightBracket
+					| TokenType::RightParen
+					| TokenType::Semicolon
+					| TokenType::String { .. }
+			);
+
+			return Some(t);
+		}
+	}
+
+	// inside parens, or after an operator
+	pub fn is_incomplete(&self) -> bool {
+		self.depth > 0 || self.continues
+	}
+
+	fn scan_token(&mut self) -> Option<Token> {
 		let mut c;
 
-		// find the next non-whitespace character
+		// find the next non-whitespace character, skipping comments
 		loop {
 			c = self.get_char()?;
 
+			if c == '#' || (c == '/' && self.consume_char('/')) {
+				self.rest_of_line();
+				continue;
+			}
+
 			// keep new lines, even though they are "whitespace"
 			if c == '\n' || !c.is_whitespace() {
 				break;
 			}
 		}
 
+		self.start = self.offset - c.len_utf8();
 		match c {
 			'(' => self.new_token(TokenType::LeftParen, 1),
 			')' => self.new_token(TokenType::RightParen, 1),
+			'[' => self.new_token(TokenType::LeftBracket, 1),
+			']' => self.new_token(TokenType::RightBracket, 1),
+			'$' => self.scan_result_ref(),
+			'"' => self.scan_string(),
 			'+' => self.scan_plus(),
 			'-' => self.scan_minus(),
 			'*' => self.scan_star(),
@@ -190,6 +291,7 @@ impl<'a> Scanner<'a> {
 			'=' => self.scan_equal(),
 			'\n' => self.scan_new_line(),
 			',' => self.new_token(TokenType::Comma, 1),
+			';' => self.new_token(TokenType::Semicolon, 1),
 			'_' => {
 				self.put_char(c);
 				self.scan_identifier()
@@ -210,6 +312,54 @@ impl<'a> Scanner<'a> {
 		}
 	}
 
+	pub fn line(&self) -> u32 {
+		self.line
+	}
+
+	// in bytes
+	pub fn offset(&self) -> usize {
+		self.offset
+	}
+
+	pub fn has_errors(&self) -> bool {
+		!self.errors.is_empty()
+	}
+
+	// also forgets any unclosed parens or trailing operator
+	pub fn skip_line(&mut self) {
+		self.rest_of_line();
+		self.end_continuation();
+	}
+
+	// forgets any unclosed parens or trailing operator, so the next new line
+	// ends the statement
+	pub fn end_continuation(&mut self) {
+		self.depth = 0;
+		self.continues = false;
+	}
+
+	pub fn rest_of_line(&mut self) -> String {
+		let mut str = String::new();
+
+		while let Some(c) = self.get_char() {
+			if c == '\n' {
+				self.put_char(c);
+				break;
+			}
+
+			str.push(c);
+		}
+
+		str
+	} // rest_of_line
+
+	// e.g. after rest_of_line, so the input is scanned again
+	pub fn put_back(&mut self, str: &str) {
+		for c in str.chars().rev() {
+			self.put_char(c);
+		}
+	}
+
 	fn scan_plus(&mut self) -> Option<Token> {
 		if self.consume_char('+') {
 			self.new_token(TokenType::PlusX2, 2)
@@ -316,10 +466,62 @@ impl<'a> Scanner<'a> {
 		}
 	}
 
+	// $$ for the latest result, or $n for result n
+	fn scan_result_ref(&mut self) -> Option<Token> {
+		let start = self.column - 1;
+		let mut str = String::new();
+
+		if self.consume_char('$') {
+			str.push('$');
+		} else {
+			while let Some(c) = self.consume_char_of(|c| c.is_ascii_digit()) {
+				str.push(c);
+			}
+		}
+
+		if str.is_empty() {
+			self.put_char('$');
+			self.unexpected_char('$');
+			return None;
+		}
+
+		self.new_token(TokenType::ResultRef { str }, self.column - start)
+	}
+
+	// "..." on one line, where \" and \\ stand for " and \
+	fn scan_string(&mut self) -> Option<Token> {
+		let start = self.column - 1;
+		let mut str = String::new();
+
+		loop {
+			match self.get_char() {
+				Some('"') => break,
+				Some('\\') => match self.get_char() {
+					Some(c) if c == '"' || c == '\\' => str.push(c),
+					Some(c) => {
+						self.error(format!("Unknown escape '\\{}' in string", c));
+						return None;
+					}
+					None => {
+						self.unexpected_end_of_input();
+						return None;
+					}
+				},
+				Some('\n') | None => {
+					self.error("Unterminated string".to_string());
+					return None;
+				}
+				Some(c) => str.push(c),
+			}
+		}
+
+		self.new_token(TokenType::String { str }, self.column - start)
+	} // scan_string
+
 	fn scan_new_line(&mut self) -> Option<Token> {
 		let t = self.new_token(TokenType::NewLine, 1);
 		self.line += 1;
-		self.column = 0;
+		self.column = 1;
 		t
 	} // scan_new_line
 
@@ -372,12 +574,99 @@ impl<'a> Scanner<'a> {
 			}
 		}
 
+		if prefix.is_empty() {
+			// dates start with exactly four year digits, e.g. 2026-10-18
+			if value.len() == 4 && self.column - start == 4 && !value.contains('.') {
+				if let Some(rest) = self.scan_date() {
+					value.push_str(&rest);
+					return self.new_token(TokenType::Date { str: value }, self.column - start);
+				}
+			}
+
+			if let Some(unit) = self.scan_unit() {
+				return self.new_token(
+					TokenType::Duration { str: value, unit },
+					self.column - start,
+				);
+			}
+		}
+
 		self.new_token(
 			TokenType::Number { str: value, prefix },
 			self.column - start,
 		)
 	} // scan_number
 
+	// Consumes input matching pattern, where '#' matches any digit. On a
+	// mismatch, all consumed input is restored.
+	fn consume_pattern(&mut self, pattern: &str) -> Option<String> {
+		let mut str = String::new();
+
+		for p in pattern.chars() {
+			match self.get_char() {
+				Some(c) if c == p || (p == '#' && c.is_ascii_digit()) => str.push(c),
+				c => {
+					if let Some(c) = c {
+						self.put_char(c);
+					}
+					for c in str.chars().rev() {
+						self.put_char(c);
+					}
+					return None;
+				}
+			}
+		}
+
+		Some(str)
+	} // consume_pattern
+
+	fn scan_date(&mut self) -> Option<String> {
+		let mut str = self.consume_pattern("-##-##")?;
+
+		if let Some(time) = self.consume_pattern("T##:##") {
+			str.push_str(&time);
+
+			if let Some(seconds) = self.consume_pattern(":##") {
+				str.push_str(&seconds);
+
+				if let Some(fraction) = self.consume_pattern(".#") {
+					str.push_str(&fraction);
+					while let Some(c) = self.consume_char_of(|c| c.is_ascii_digit()) {
+						str.push(c);
+					}
+				}
+			}
+
+			if self.consume_char('Z') {
+				str.push('Z');
+			} else if let Some(offset) = self
+				.consume_pattern("+##:##")
+				.or_else(|| self.consume_pattern("-##:##"))
+			{
+				str.push_str(&offset);
+			}
+		}
+
+		Some(str)
+	} // scan_date
+
+	fn scan_unit(&mut self) -> Option<String> {
+		let mut unit = String::new();
+		while let Some(c) = self.consume_char_of(|c| c == '_' || c.is_alphanumeric()) {
+			unit.push(c);
+		}
+
+		if datetime::unit_millis(&unit).is_some() {
+			return Some(unit);
+		}
+
+		for c in unit.chars().rev() {
+			self.put_char(c);
+		}
+
+		None
+	} // scan_unit
+
 	fn scan_identifier(&mut self) -> Option<Token> {
 		let start = self.column;
 		let mut str = String::new();
@@ -386,6 +675,21 @@ impl<'a> Scanner<'a> {
 		while let Some(c) = self.get_char() {
 			match c {
 				'_' => {}
+				// namespace member access, e.g. phys.c
+				'.' => match self.get_char() {
+					Some(n) if n == '_' || n.is_alphabetic() => {
+						str.push(c);
+						str.push(n);
+						continue;
+					}
+					n => {
+						if let Some(n) = n {
+							self.put_char(n);
+						}
+						self.put_char(c);
+						break;
+					}
+				},
 				_ => {
 					if !c.is_alphanumeric() {
 						self.put_char(c);
@@ -405,7 +709,7 @@ impl<'a> Scanner<'a> {
 mod tests {
 	use crate::scanning::*;
 
-	fn setup(input: &str) -> Scanner {
+	fn setup(input: &str) -> Scanner<'_> {
 		Scanner::new(input)
 	}
 
@@ -447,6 +751,91 @@ mod tests {
 		expect(&mut setup(","), TokenType::Comma);
 	}
 
+	#[test]
+	fn scan_date() {
+		let mut s = setup("2026-10-18 2026-10-18T12:00Z 2026-10-18T12:00:30.5-05:00 2026-10-1");
+		expect(
+			&mut s,
+			TokenType::Date {
+				str: "2026-10-18".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::Date {
+				str: "2026-10-18T12:00Z".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::Date {
+				str: "2026-10-18T12:00:30.5-05:00".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::Number {
+				str: "2026".to_string(),
+				prefix: "".to_string(),
+			},
+		);
+		expect(&mut s, TokenType::Minus);
+	}
+
+	#[test]
+	fn scan_positions_at_end() {
+		// the column and length of each token
+		let positions = |input| {
+			let mut s = setup(input);
+			std::iter::from_fn(move || s.next())
+				.map(|t| (t.column, t.length))
+				.collect::<Vec<_>>()
+		};
+
+		// lookahead past the end of input must not move the column
+		assert_eq!(positions("1 + 2026-10"), [(1, 1), (3, 1), (5, 4), (9, 1), (10, 2)]);
+		assert_eq!(positions("2 * phys."), [(1, 1), (3, 1), (5, 4), (9, 1)]);
+	}
+
+	#[test]
+	fn scan_duration() {
+		let mut s = setup("3d 1.5h 90min 3days");
+		expect(
+			&mut s,
+			TokenType::Duration {
+				str: "3".to_string(),
+				unit: "d".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::Duration {
+				str: "1.5".to_string(),
+				unit: "h".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::Duration {
+				str: "90".to_string(),
+				unit: "min".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::Number {
+				str: "3".to_string(),
+				prefix: "".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::Identifier {
+				str: "days".to_string(),
+			},
+		);
+	}
+
 	#[test]
 	fn scan_equal() {
 		expect(&mut setup("="), TokenType::Equal);
@@ -477,6 +866,36 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn scan_identifier_member() {
+		let mut s = setup("phys.k_B x.5 y.");
+		expect(
+			&mut s,
+			TokenType::Identifier {
+				str: "phys.k_B".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::Identifier {
+				str: "x".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::Number {
+				str: ".5".to_string(),
+				prefix: "".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::Identifier {
+				str: "y".to_string(),
+			},
+		);
+	}
+
 	#[test]
 	fn scan_left_angle_bracket_x2() {
 		expect(&mut setup("<<"), TokenType::LeftAngleBracketX2);
@@ -512,6 +931,131 @@ mod tests {
 		expect(&mut setup("\n"), TokenType::NewLine);
 	}
 
+	#[test]
+	fn scan_result_ref() {
+		let mut s = setup("$$ $12 ans[-2]");
+		expect(
+			&mut s,
+			TokenType::ResultRef {
+				str: "$".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::ResultRef {
+				str: "12".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::Identifier {
+				str: "ans".to_string(),
+			},
+		);
+		expect(&mut s, TokenType::LeftBracket);
+		expect(&mut s, TokenType::Minus);
+		expect(
+			&mut s,
+			TokenType::Number {
+				str: "2".to_string(),
+				prefix: "".to_string(),
+			},
+		);
+		expect(&mut s, TokenType::RightBracket);
+	}
+
+	#[test]
+	fn scan_continued_line() {
+		let mut s = setup("(1\n) +\n2\n");
+		expect(&mut s, TokenType::LeftParen);
+		expect(
+			&mut s,
+			TokenType::Number {
+				str: "1".to_string(),
+				prefix: "".to_string(),
+			},
+		);
+		expect(&mut s, TokenType::RightParen);
+		expect(&mut s, TokenType::Plus);
+		assert!(s.is_incomplete());
+		expect(
+			&mut s,
+			TokenType::Number {
+				str: "2".to_string(),
+				prefix: "".to_string(),
+			},
+		);
+		assert!(!s.is_incomplete());
+		expect(&mut s, TokenType::NewLine);
+		assert!(s.next().is_none());
+
+		// a++ and x-- end a line, like other operands
+		let mut s = setup("a++
+x--
+");
+		expect(&mut s, TokenType::Identifier { str: "a".to_string() });
+		expect(&mut s, TokenType::PlusX2);
+		assert!(!s.is_incomplete());
+		expect(&mut s, TokenType::NewLine);
+		expect(&mut s, TokenType::Identifier { str: "x".to_string() });
+		expect(&mut s, TokenType::MinusX2);
+		assert!(!s.is_incomplete());
+		expect(&mut s, TokenType::NewLine);
+		assert!(s.next().is_none());
+	}
+
+	#[test]
+	fn scan_string() {
+		let mut s = setup(r#""HOME" "a \"b\" \\""#);
+		expect(
+			&mut s,
+			TokenType::String {
+				str: "HOME".to_string(),
+			},
+		);
+		expect(
+			&mut s,
+			TokenType::String {
+				str: r#"a "b" \"#.to_string(),
+			},
+		);
+
+		let mut s = setup("\"abc\n\"");
+		assert!(s.next().is_none());
+		assert_eq!(
+			s.take_errors(),
+			vec![SyntaxError::new("Unterminated string".to_string(), Some((1, 6)))]
+		);
+	}
+
+	#[test]
+	fn scan_comments() {
+		let mut s = setup("#!/usr/bin/env tcalc\n1 // one\n2; # two\n");
+		expect(&mut s, TokenType::NewLine);
+		expect(
+			&mut s,
+			TokenType::Number {
+				str: "1".to_string(),
+				prefix: "".to_string(),
+			},
+		);
+		expect(&mut s, TokenType::NewLine);
+		expect(
+			&mut s,
+			TokenType::Number {
+				str: "2".to_string(),
+				prefix: "".to_string(),
+			},
+		);
+		expect(&mut s, TokenType::Semicolon);
+		expect(&mut s, TokenType::NewLine);
+		assert!(s.next().is_none());
+
+		let mut s = setup("4 / 2");
+		s.next();
+		expect(&mut s, TokenType::ForwardSlash);
+	}
+
 	#[test]
 	fn scan_number() {
 		let mut s = setup("0b01 0o01234567 0x0123456789abcdefABCDEF 0123456789 11_11 11.11");
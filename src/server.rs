@@ -0,0 +1,427 @@
+// a JSON-RPC 2.0 server with one request or response per line; the methods are
+// listed in the README
+
+use serde_json::{json, Value as Json};
+use std::io::{self, BufRead, Write};
+#[cfg(unix)]
+use std::{io::BufReader, os::unix::net::UnixListener, path::Path, sync::Arc, thread};
+
+use crate::ast::*;
+use crate::completion::NameCompleter;
+use crate::json;
+use crate::parsing;
+use crate::running::*;
+use crate::scanning::SyntaxError;
+use crate::value::*;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+// input which failed to parse or evaluate
+const EVAL_ERROR: i64 = 1;
+
+pub type NewRunner = dyn Fn() -> Result<Runner, String> + Send + Sync;
+
+pub struct RpcError {
+	code: i64,
+	message: String,
+	data: Option<Json>,
+}
+
+impl RpcError {
+	pub fn new(code: i64, message: String) -> RpcError {
+		RpcError {
+			code,
+			message,
+			data: None,
+		}
+	}
+
+	fn eval(e: SyntaxError) -> RpcError {
+		RpcError {
+			code: EVAL_ERROR,
+			data: Some(json::error(&e)),
+			message: e.message,
+		}
+	}
+}
+
+pub fn response(id: Json, result: Result<Json, RpcError>) -> Json {
+	match result {
+		Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+		Err(e) => {
+			let mut error = json!({ "code": e.code, "message": e.message });
+			if let Some(data) = e.data {
+				error["data"] = data;
+			}
+			json!({ "jsonrpc": "2.0", "id": id, "error": error })
+		}
+	}
+}
+
+pub struct Session<'a> {
+	runner: Runner,
+	new_runner: &'a NewRunner,
+	format: Format,
+}
+
+impl<'a> Session<'a> {
+	pub fn new(new_runner: &'a NewRunner, format: Format) -> Result<Session<'a>, String> {
+		Ok(Session {
+			runner: new_runner()?,
+			new_runner,
+			format,
+		})
+	}
+
+	fn string_param(params: &Json, name: &str) -> Result<String, RpcError> {
+		match params.get(name) {
+			Some(Json::String(str)) => Ok(str.clone()),
+			_ => Err(RpcError::new(
+				INVALID_PARAMS,
+				format!("Expected a string parameter \"{}\"", name),
+			)),
+		}
+	}
+
+	fn eval(&mut self, params: &Json) -> Result<Json, RpcError> {
+		let expr = Self::string_param(params, "expr")?;
+
+		let ast = parsing::try_parse_located(&expr).map_err(RpcError::eval)?;
+		let failed = |msg| RpcError::eval(SyntaxError::new(msg, None));
+
+		match ast {
+			Some(Ast::Command(_)) => Err(RpcError::new(
+				INVALID_PARAMS,
+				"Commands cannot be evaluated; use the vars and reset methods".to_string(),
+			)),
+			Some(Ast::Expression(expr)) => {
+				let (number, value) = self.runner.run_numbered(&expr).map_err(failed)?;
+				Ok(json!({
+					"value": json::value(&value, &self.format),
+					"number": number,
+				}))
+			}
+			Some(Ast::Statement(stmt)) => {
+				self.runner.run_statement(&stmt).map_err(failed)?;
+				Ok(json!({ "value": null, "number": null }))
+			}
+			None => Ok(json!({ "value": null, "number": null })),
+		}
+	}
+
+	fn define(&mut self, params: &Json) -> Result<Json, RpcError> {
+		let name = Self::string_param(params, "name")?;
+		if !matches!(parsing::try_parse(&name), Ok(Some(Ast::Expression(Expression::Variable(_))))) {
+			return Err(RpcError::new(
+				INVALID_PARAMS,
+				format!("\"{}\" is not a variable name", name),
+			));
+		}
+
+		// numbers are taken as they are, and strings are read as expressions, which
+		// may not assign other variables
+		let value = match params.get("value") {
+			Some(Json::Number(n)) => match n.as_i64() {
+				Some(n) => Value::integer(n as i128),
+				None => Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+			},
+			Some(Json::String(expr)) => match parsing::try_parse_located(expr).map_err(RpcError::eval)? {
+				Some(Ast::Expression(expr)) if expr.assigns() => {
+					return Err(RpcError::new(
+						INVALID_PARAMS,
+						"Expected \"value\" to be an expression without assignments".to_string(),
+					))
+				}
+				Some(Ast::Expression(expr)) => self
+					.runner
+					.evaluate(&expr)
+					.map_err(|msg| RpcError::eval(SyntaxError::new(msg, None)))?,
+				_ => {
+					return Err(RpcError::new(
+						INVALID_PARAMS,
+						"Expected \"value\" to be an expression".to_string(),
+					))
+				}
+			},
+			_ => {
+				return Err(RpcError::new(
+					INVALID_PARAMS,
+					"Expected a number or string parameter \"value\"".to_string(),
+				))
+			}
+		};
+
+		self.runner
+			.define(&name, value.clone())
+			.map_err(|msg| RpcError::eval(SyntaxError::new(msg, None)))?;
+
+		Ok(json!({ "value": json::value(&value, &self.format) }))
+	} // define
+
+	fn complete(&self, params: &Json) -> Result<Json, RpcError> {
+		let text = Self::string_param(params, "text")?;
+		let pos = match params.get("pos") {
+			None => text.len(),
+			Some(pos) => match pos.as_u64() {
+				Some(pos) if text.is_char_boundary(pos as usize) => pos as usize,
+				_ => {
+					return Err(RpcError::new(
+						INVALID_PARAMS,
+						"Expected \"pos\" to be a byte offset into \"text\"".to_string(),
+					))
+				}
+			},
+		};
+
+		let mut completer = NameCompleter::new();
+		completer.update_vars(&self.runner);
+		let (start, items) = completer.complete(&text, pos);
+
+		Ok(json!({ "start": start, "items": items }))
+	}
+
+	fn call(&mut self, method: &str, params: &Json) -> Result<Json, RpcError> {
+		match method {
+			"complete" => self.complete(params),
+			"define" => self.define(params),
+			"eval" => self.eval(params),
+			"reset" => {
+				self.runner = (self.new_runner)().map_err(|msg| RpcError::new(EVAL_ERROR, msg))?;
+				Ok(Json::Null)
+			}
+			"vars" => Ok(json::vars(&self.runner, &self.format)),
+			_ => Err(RpcError::new(
+				METHOD_NOT_FOUND,
+				format!("Unknown method \"{}\"", method),
+			)),
+		}
+	}
+
+	// returns the response to one request, or None for notifications
+	fn handle_request(&mut self, request: &Json) -> Option<Json> {
+		let id = request.get("id");
+		let valid_id = matches!(id, None | Some(Json::Null | Json::Number(_) | Json::String(_)));
+		let method = request.get("method").and_then(Json::as_str);
+		let params = request.get("params").cloned().unwrap_or(Json::Null);
+
+		match method {
+			Some(method) if valid_id && request.get("jsonrpc") == Some(&json!("2.0")) => {
+				let result = self.call(method, &params);
+
+				// notifications have no id, and get no response
+				id.map(|id| response(id.clone(), result))
+			}
+			_ => {
+				let id = id.filter(|_| valid_id).cloned().unwrap_or(Json::Null);
+				Some(response(
+					id,
+					Err(RpcError::new(
+						INVALID_REQUEST,
+						"Expected a JSON-RPC 2.0 request".to_string(),
+					)),
+				))
+			}
+		}
+	} // handle_request
+
+	// there is no response when the line only had notifications
+	pub fn handle(&mut self, line: &str) -> Option<String> {
+		let response = match serde_json::from_str::<Json>(line) {
+			Ok(Json::Array(requests)) if requests.is_empty() => Some(response(
+				Json::Null,
+				Err(RpcError::new(INVALID_REQUEST, "Expected a non-empty batch".to_string())),
+			)),
+			Ok(Json::Array(requests)) => {
+				let responses: Vec<Json> = requests
+					.iter()
+					.filter_map(|request| self.handle_request(request))
+					.collect();
+				(!responses.is_empty()).then_some(Json::Array(responses))
+			}
+			Ok(request) => self.handle_request(&request),
+			Err(msg) => Some(response(Json::Null, Err(RpcError::new(PARSE_ERROR, msg.to_string())))),
+		};
+
+		response.map(|response| response.to_string())
+	} // handle
+} // Session
+
+pub fn serve<R: BufRead, W: Write>(
+	input: R,
+	mut output: W,
+	new_runner: &NewRunner,
+	format: Format,
+) -> Result<(), String> {
+	let mut session = Session::new(new_runner, format)?;
+
+	for line in input.lines() {
+		let line = line.map_err(|msg| format!("Failed to read request: {}", msg))?;
+		if line.trim().is_empty() {
+			continue;
+		}
+
+		if let Some(response) = session.handle(&line) {
+			writeln!(output, "{}", response)
+				.and_then(|_| output.flush())
+				.map_err(|msg| format!("Failed to write response: {}", msg))?;
+		}
+	}
+
+	Ok(())
+}
+
+pub fn serve_stdio(new_runner: &NewRunner, format: Format) -> Result<(), String> {
+	serve(io::stdin().lock(), io::stdout().lock(), new_runner, format)
+}
+
+// each connection is served on its own thread
+#[cfg(unix)]
+pub fn serve_socket(path: &Path, new_runner: Arc<NewRunner>, format: Format) -> Result<(), String> {
+	let listener = UnixListener::bind(path)
+		.map_err(|msg| format!("Failed to listen on '{}': {}", path.display(), msg))?;
+
+	for stream in listener.incoming() {
+		let stream = match stream {
+			Ok(stream) => stream,
+			Err(msg) => {
+				eprintln!("Failed to accept a connection: {}", msg);
+				continue;
+			}
+		};
+
+		let new_runner = Arc::clone(&new_runner);
+		thread::spawn(move || {
+			let input = match stream.try_clone() {
+				Ok(input) => BufReader::new(input),
+				Err(msg) => return eprintln!("Failed to read from a connection: {}", msg),
+			};
+
+			if let Err(msg) = serve(input, stream, new_runner.as_ref(), format) {
+				eprintln!("{}", msg);
+			}
+		});
+	}
+
+	Ok(())
+} // serve_socket
+
+#[cfg(test)]
+mod tests {
+	use crate::server::*;
+
+	fn new_runner() -> Result<Runner, String> {
+		Ok(Runner::new())
+	}
+
+	fn call(session: &mut Session, request: &str) -> Json {
+		serde_json::from_str(&session.handle(request).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn serve_requests() {
+		let mut session = Session::new(&new_runner, Format::default()).unwrap();
+		let two = json!({ "type": "number", "value": 2.0, "text": "2" });
+
+		assert_eq!(
+			call(&mut session, r#"{"jsonrpc": "2.0", "id": 1, "method": "eval", "params": {"expr": "x = 1 + 1"}}"#),
+			json!({ "jsonrpc": "2.0", "id": 1, "result": { "value": two, "number": 1 } })
+		);
+		assert_eq!(
+			call(&mut session, r#"{"jsonrpc": "2.0", "id": 2, "method": "define", "params": {"name": "y", "value": "x * 3"}}"#)["result"]["value"]["value"],
+			json!(6.0)
+		);
+		assert_eq!(
+			call(&mut session, r#"{"jsonrpc": "2.0", "id": 3, "method": "vars"}"#)["result"]["x"],
+			two
+		);
+		assert_eq!(
+			call(&mut session, r#"{"jsonrpc": "2.0", "id": 4, "method": "complete", "params": {"text": "2 * y + gc"}}"#)["result"],
+			json!({ "start": 8, "items": ["gcd("] })
+		);
+		assert_eq!(
+			session.handle(r#"{"jsonrpc": "2.0", "method": "reset"}"#),
+			None
+		);
+		assert_eq!(
+			call(&mut session, r#"{"jsonrpc": "2.0", "id": 5, "method": "vars"}"#)["result"],
+			json!({})
+		);
+	}
+
+	#[test]
+	fn serve_errors() {
+		let mut session = Session::new(&new_runner, Format::default()).unwrap();
+
+		assert_eq!(
+			call(&mut session, r#"{"jsonrpc": "2.0", "id": 1, "method": "eval", "params": {"expr": "1 +* 2"}}"#)["error"],
+			json!({
+				"code": 1,
+				"message": "Unexpected token 'Star'",
+				"data": { "message": "Unexpected token 'Star'", "line": 1, "column": 4 },
+			})
+		);
+		assert_eq!(
+			call(&mut session, r#"{"jsonrpc": "2.0", "id": 2, "method": "define", "params": {"name": "pi", "value": 3}}"#)["error"]["message"],
+			json!("Cannot assign to constant \"pi\"")
+		);
+		assert_eq!(
+			call(&mut session, r#"{"jsonrpc": "2.0", "id": 3, "method": "define", "params": {"name": "1 + 2", "value": 3}}"#)["error"]["code"],
+			json!(INVALID_PARAMS)
+		);
+
+		// the value may not assign other variables on the way
+		for value in ["b = 3", "1 + (b = 3)", "b++"] {
+			let request = json!({"jsonrpc": "2.0", "id": 3, "method": "define", "params": {"name": "a", "value": value}});
+			assert_eq!(call(&mut session, &request.to_string())["error"]["code"], json!(INVALID_PARAMS));
+		}
+		assert_eq!(
+			call(&mut session, r#"{"jsonrpc": "2.0", "id": 3, "method": "vars"}"#)["result"],
+			json!({})
+		);
+		assert_eq!(
+			call(&mut session, r#"{"jsonrpc": "2.0", "id": 4, "method": "frobnicate"}"#)["error"]["code"],
+			json!(METHOD_NOT_FOUND)
+		);
+		assert_eq!(call(&mut session, "{")["error"]["code"], json!(PARSE_ERROR));
+		assert_eq!(
+			call(&mut session, r#"{"id": 5, "method": "vars"}"#)["error"]["code"],
+			json!(INVALID_REQUEST)
+		);
+
+		// invalid requests are answered with a null id, even without one
+		let invalid = json!({
+			"jsonrpc": "2.0",
+			"id": null,
+			"error": { "code": INVALID_REQUEST, "message": "Expected a JSON-RPC 2.0 request" },
+		});
+		assert_eq!(call(&mut session, r#"{"method": "vars"}"#), invalid);
+		assert_eq!(call(&mut session, r#""x""#), invalid);
+		assert_eq!(call(&mut session, r#"{"jsonrpc": "2.0", "id": [1], "method": "vars"}"#), invalid);
+		assert_eq!(call(&mut session, "[]")["error"]["code"], json!(INVALID_REQUEST));
+	}
+
+	#[test]
+	fn serve_batches() {
+		let mut session = Session::new(&new_runner, Format::default()).unwrap();
+
+		let responses = call(
+			&mut session,
+			r#"[
+				{"jsonrpc": "2.0", "method": "define", "params": {"name": "x", "value": 2}},
+				{"jsonrpc": "2.0", "id": "a", "method": "eval", "params": {"expr": "x * 3"}},
+				1
+			]"#,
+		);
+		assert_eq!(responses[0]["id"], json!("a"));
+		assert_eq!(responses[0]["result"]["value"]["value"], json!(6.0));
+		assert_eq!(responses[1]["error"]["code"], json!(INVALID_REQUEST));
+		assert_eq!(responses.as_array().map(Vec::len), Some(2));
+
+		assert_eq!(
+			session.handle(r#"[{"jsonrpc": "2.0", "method": "reset"}]"#),
+			None
+		);
+	}
+}
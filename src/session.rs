@@ -0,0 +1,190 @@
+use crate::ast::*;
+use crate::parsing;
+use crate::running::*;
+use crate::value::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn cache_path(file_name: &str) -> Option<PathBuf> {
+	let mut path = dirs::cache_dir()?;
+	path.push(file_name);
+	Some(path)
+}
+
+pub fn default_path() -> Option<PathBuf> {
+	cache_path("tcalc_session")
+}
+
+// autosave is enabled for as long as this file exists
+pub fn autosave_path() -> Option<PathBuf> {
+	cache_path("tcalc_autosave")
+}
+
+pub fn init_path() -> Option<PathBuf> {
+	let mut path = dirs::config_dir()?;
+	path.push("tcalc");
+	path.push("init.tc");
+	Some(path)
+}
+
+pub fn expand_path(path: &str) -> PathBuf {
+	if let Some(rest) = path.strip_prefix("~/") {
+		if let Some(mut home) = dirs::home_dir() {
+			home.push(rest);
+			return home;
+		}
+	}
+
+	PathBuf::from(path)
+}
+
+// values are saved as literals, so only values with a literal form can be saved
+fn is_saveable(value: &Value) -> bool {
+	match value {
+		Value::Date(_) | Value::Duration(_) | Value::Integer(_) | Value::String(_) => true,
+		Value::List(_) => false,
+		Value::Number(n) => n.is_finite(),
+	}
+}
+
+// saved as statements which recreate the session when loaded
+pub fn save(runner: &Runner, path: &Path) -> Result<(), String> {
+	let mut out = String::new();
+
+	for scope in runner.scopes().iter().skip(1) {
+		// ans is written last, since every loaded expression overwrites it
+		let mut names: Vec<&String> = scope.keys().collect();
+		names.sort_by_key(|name| (*name == "ans", *name));
+
+		for name in names {
+			let value = &scope[name];
+			if !is_saveable(value) {
+				println!("Skipping \"{}\"; {} cannot be saved", name, value);
+				continue;
+			}
+
+			if runner.is_const(name) {
+				out.push_str("const ");
+			}
+			out.push_str(&format!("{} = {}\n", name, value));
+		}
+	}
+
+	for (number, value) in runner.results() {
+		if is_saveable(value) {
+			out.push_str(&format!("${} = {}\n", number, value));
+		} else {
+			println!("Skipping \"${}\"; {} cannot be saved", number, value);
+		}
+	}
+
+	fs::write(path, out)
+		.map_err(|msg| format!("Failed to save '{}': {}", path.display(), msg))
+}
+
+// errors are printed with their line number, and loading carries on
+pub fn load(runner: &mut Runner, path: &Path) -> Result<(), String> {
+	let text = fs::read_to_string(path)
+		.map_err(|msg| format!("Failed to load '{}': {}", path.display(), msg))?;
+
+	for (line, ast) in parsing::parse_script(&text) {
+		let result = ast.and_then(|ast| match ast {
+			Ast::Expression(expr) => runner.run_expression(&expr).map(|_| ()),
+			Ast::Statement(stmt) => runner.run_statement(&stmt),
+			Ast::Command(_) => Ok(()),
+		});
+
+		if let Err(msg) = result {
+			eprintln!("{}:{}: {}", path.display(), line, msg);
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::session::*;
+	use std::env;
+
+	fn run(runner: &mut Runner, input: &str) {
+		match parsing::parse(input) {
+			Some(Ast::Expression(expr)) => runner.run_numbered(&expr).map(|_| ()),
+			Some(Ast::Statement(stmt)) => runner.run_statement(&stmt),
+			ast => panic!("Unexpected Ast for input \"{}\": {:?}", input, ast),
+		}
+		.unwrap();
+	}
+
+	#[test]
+	fn save_load() {
+		let mut runner = Runner::new();
+		for input in [
+			"a = -1.25",
+			"const rate = 0.5",
+			"due = 2026-10-18T12:00Z",
+			"window = -(1h 30min)",
+			"f = factor(12)",
+			"b = 7",
+		] {
+			run(&mut runner, input);
+		}
+
+		let path = env::temp_dir().join(format!("tcalc_test_{}", std::process::id()));
+		save(&runner, &path).unwrap();
+		assert_eq!(
+			fs::read_to_string(&path).unwrap(),
+			"a = -1.25\nb = 7\ndue = 2026-10-18T12:00:00Z\nconst rate = 0.5\nwindow = -1h 30min\nans = 7\n\
+			$1 = -1.25\n$2 = 2026-10-18T12:00:00Z\n$3 = -1h 30min\n$5 = 7\n"
+		);
+
+		let mut loaded = Runner::new();
+		load(&mut loaded, &path).unwrap();
+		fs::remove_file(&path).unwrap();
+
+		let mut expected = runner.scopes()[1].clone();
+		expected.remove("f");
+		assert_eq!(loaded.scopes()[1], expected);
+		assert!(loaded.is_const("rate"));
+
+		let mut expected = runner.results().clone();
+		expected.remove(&4);
+		assert_eq!(loaded.results(), &expected);
+	}
+
+	#[test]
+	fn save_load_date_range() {
+		let mut runner = Runner::new();
+		run(&mut runner, "first = 0000-01-01");
+		run(&mut runner, "last = 9999-12-31T23:59:59.999Z");
+
+		let path = env::temp_dir().join(format!("tcalc_test_range_{}", std::process::id()));
+		save(&runner, &path).unwrap();
+		let mut loaded = Runner::new();
+		load(&mut loaded, &path).unwrap();
+		fs::remove_file(&path).unwrap();
+
+		assert_eq!(loaded.scopes()[1], runner.scopes()[1]);
+	}
+
+	#[test]
+	fn load_over_init_script() {
+		// the init script runs before the autosaved session is loaded, and a
+		// constant it declares is saved with the session, so loading declares
+		// it again; the same value is accepted, and a changed one is reported
+		let path = env::temp_dir().join(format!("tcalc_test_init_{}", std::process::id()));
+		fs::write(&path, "const rate = 0.5\nb = rate * 2\n").unwrap();
+
+		for (init, rate, b) in [("const rate = 0.5", 0.5, 1f64), ("const rate = 0.25", 0.25, 0.5)] {
+			let mut runner = Runner::new();
+			run(&mut runner, init);
+			load(&mut runner, &path).unwrap();
+
+			assert!(runner.is_const("rate"));
+			assert_eq!(runner.scopes()[1].get("rate"), Some(&Value::Number(rate)));
+			assert_eq!(runner.scopes()[1].get("b"), Some(&Value::Number(b)));
+		}
+
+		fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,166 @@
+// text where each {{ }} block is replaced by its value, e.g. "{{ 2**10 }}"
+
+use crate::ast::*;
+use crate::parsing;
+use crate::running::*;
+use crate::scanning::*;
+use crate::value::*;
+
+// the text a block is replaced by; strings are written without their quotes
+fn block_text(value: &Value, format: &Format) -> String {
+	match value {
+		Value::String(str) => str.clone(),
+		_ => format.value(value),
+	}
+}
+
+// finds the "}}" which closes a block, given the text after its "{{"; the block
+// is scanned as tokens, so a "}}" inside a string does not close it
+fn block_end(source: &str) -> Option<usize> {
+	let mut start = 0;
+
+	loop {
+		let mut scanner = Scanner::new(&source[start..]);
+		while scanner.next().is_some() {}
+
+		let end = start + scanner.offset();
+		let rest = &source[end..];
+		if rest.starts_with("}}") {
+			return Some(end);
+		}
+
+		// the scanner stopped at a character it could not scan, which is
+		// reported when the block runs
+		start = end + rest.chars().next()?.len_utf8();
+	}
+}
+
+// runs the statements in a block, returning the value of the last one, or the
+// line of the block where it failed along with the error
+fn run_block(runner: &mut Runner, source: &str, format: &Format) -> Result<String, (u32, String)> {
+	let mut out = String::new();
+
+	for (line, ast) in parsing::parse_script_located(source) {
+		let fail = |msg| (line, msg);
+
+		// the template line is reported, so the position in the block is not
+		let ast = ast.map_err(|e| (e.position.map_or(line, |(line, _)| line), e.message))?;
+		out = match ast {
+			Ast::Command(_) => return Err(fail("Commands cannot be used in templates".to_string())),
+			// assignments only define variables, so they write nothing
+			Ast::Expression(expr @ Expression::Assignment(_)) => {
+				runner.run_numbered(&expr).map_err(fail)?;
+				String::new()
+			}
+			Ast::Expression(expr) => {
+				let (_, value) = runner.run_numbered(&expr).map_err(fail)?;
+				block_text(&value, format)
+			}
+			Ast::Statement(stmt) => {
+				runner.run_statement(&stmt).map_err(fail)?;
+				String::new()
+			}
+		};
+	}
+
+	Ok(out)
+}
+
+// failed blocks are left as they were, and their errors returned with the line
+pub fn render(runner: &mut Runner, text: &str, format: &Format) -> (String, Vec<(u32, String)>) {
+	let mut out = String::with_capacity(text.len());
+	let mut errors = vec![];
+	let mut rest = text;
+	let mut line = 1;
+
+	while let Some(start) = rest.find("{{") {
+		out.push_str(&rest[..start]);
+		line += rest[..start].matches('\n').count() as u32;
+
+		let end = unwrap!(block_end(&rest[start + 2..]), {
+			errors.push((line, "Unclosed \"{{\"".to_string()));
+			rest = &rest[start..];
+			break;
+		});
+
+		let block = &rest[start..start + end + 4];
+		match run_block(runner, &block[2..block.len() - 2], format) {
+			Ok(text) => out.push_str(&text),
+			Err((block_line, msg)) => {
+				errors.push((line + block_line - 1, msg));
+				out.push_str(block);
+			}
+		}
+
+		line += block.matches('\n').count() as u32;
+		rest = &rest[start + end + 4..];
+	}
+	out.push_str(rest);
+
+	(out, errors)
+} // render
+
+#[cfg(test)]
+mod tests {
+	use crate::template::*;
+
+	fn run(text: &str) -> (String, Vec<(u32, String)>) {
+		render(&mut Runner::new(), text, &Format::default())
+	}
+
+	#[test]
+	fn render_blocks() {
+		assert_eq!(
+			run("size: {{ 2**10 }}\n{{ rate = 0.5 }}half: {{ 10 * rate }}, {{ \"MB\" }}"),
+			("size: 1024\nhalf: 5, MB".to_string(), vec![])
+		);
+		assert_eq!(
+			run("{{ a = 2; b = 3\na * b }} {{ const c = 1 }}."),
+			("6 .".to_string(), vec![])
+		);
+		assert_eq!(run("no blocks"), ("no blocks".to_string(), vec![]));
+		assert_eq!(run("{{ \"}}\" }}, {{ \"{{\" }}"), ("}}, {{".to_string(), vec![]));
+
+		let mut runner = Runner::new();
+		let format = Format {
+			precision: Some(2),
+			..Format::default()
+		};
+		assert_eq!(render(&mut runner, "{{ 1 / 3 }}", &format).0, "0.33");
+	}
+
+	#[test]
+	fn render_errors() {
+		assert_eq!(
+			run("a\n{{ 1 / 0 }} {{ 2 }}\n{{\n\nx }}"),
+			(
+				"a\n{{ 1 / 0 }} 2\n{{\n\nx }}".to_string(),
+				vec![
+					(2, "Cannot divide by zero".to_string()),
+					(5, "Variable \"x\" is undefined".to_string()),
+				]
+			)
+		);
+		assert_eq!(
+			run("{{ vars }}"),
+			(
+				"{{ vars }}".to_string(),
+				vec![(1, "Commands cannot be used in templates".to_string())]
+			)
+		);
+		assert_eq!(
+			run("{{ 1 +\n\n* 2 }} {{ 1 @ 2 }}"),
+			(
+				"{{ 1 +\n\n* 2 }} {{ 1 @ 2 }}".to_string(),
+				vec![
+					(3, "Unexpected token 'Star'".to_string()),
+					(3, "Unexpected character '@'".to_string()),
+				]
+			)
+		);
+		assert_eq!(
+			run("x\n{{ 1"),
+			("x\n{{ 1".to_string(), vec![(2, "Unclosed \"{{\"".to_string())])
+		);
+	}
+}
@@ -0,0 +1,231 @@
+use crate::ast::Notation;
+use crate::datetime;
+use crate::math;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+	Date(i64),
+	Duration(i64),
+	// integers too large for an f64 to hold exactly; smaller ones are Numbers
+	Integer(i128),
+	List(Vec<Value>),
+	Number(f64),
+	String(String),
+}
+
+impl Value {
+	pub fn date(millis: i64) -> Result<Value, String> {
+		if !(datetime::MIN_DATE_MILLIS..=datetime::MAX_DATE_MILLIS).contains(&millis) {
+			Err("Date is out of range".to_string())
+		} else {
+			Ok(Value::Date(millis))
+		}
+	}
+
+	pub fn duration(millis: f64) -> Result<Value, String> {
+		if !millis.is_finite() || millis.abs() > datetime::MAX_DURATION_MILLIS as f64 {
+			Err("Duration is out of range".to_string())
+		} else {
+			Ok(Value::Duration(millis.round() as i64))
+		}
+	}
+
+	pub fn integer(n: i128) -> Value {
+		if n.unsigned_abs() < math::MAX_EXACT_INT as u128 {
+			Value::Number(n as f64)
+		} else {
+			Value::Integer(n)
+		}
+	}
+
+	// Numbers from 2^53 on may have been rounded, so they are not exact
+	pub fn exact_integer(&self) -> Option<i128> {
+		match self {
+			Value::Integer(n) => Some(*n),
+			Value::Number(n) if n.fract() == 0f64 && n.abs() < math::MAX_EXACT_INT as f64 => {
+				Some(*n as i128)
+			}
+			_ => None,
+		}
+	}
+
+	pub fn number(&self) -> Option<f64> {
+		match self {
+			Value::Integer(n) => Some(*n as f64),
+			Value::Number(n) => Some(*n),
+			_ => None,
+		}
+	}
+
+	pub fn type_name(&self) -> &'static str {
+		match self {
+			Value::Date(_) => "date",
+			Value::Duration(_) => "duration",
+			Value::Integer(_) | Value::Number(_) => "number",
+			Value::List(_) => "list",
+			Value::String(_) => "string",
+		}
+	}
+} // Value
+
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Value::Date(millis) => write!(f, "{}", datetime::format_date(*millis)),
+			Value::Duration(millis) => write!(f, "{}", datetime::format_duration(*millis)),
+			Value::Integer(n) => write!(f, "{}", n),
+			Value::List(items) => {
+				write!(f, "[")?;
+				for (i, item) in items.iter().enumerate() {
+					if i > 0 {
+						write!(f, ", ")?;
+					}
+					write!(f, "{}", item)?;
+				}
+				write!(f, "]")
+			}
+			Value::Number(n) => write!(f, "{}", n),
+			// quoted like the literal, so strings are saved as they are written
+			Value::String(str) => {
+				let escaped = str.replace('\\', "\\\\").replace('"', "\\\"");
+				write!(f, "\"{}\"", escaped)
+			}
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Format {
+	pub notation: Notation,
+	pub precision: Option<usize>,
+	// only integers are printed in other bases
+	pub radix: u32,
+}
+
+impl Default for Format {
+	fn default() -> Format {
+		Format {
+			notation: Notation::Auto,
+			precision: None,
+			radix: 10,
+		}
+	}
+}
+
+fn trim_zeros(str: &str) -> &str {
+	if str.contains('.') {
+		str.trim_end_matches('0').trim_end_matches('.')
+	} else {
+		str
+	}
+}
+
+impl Format {
+	pub fn number(&self, n: f64) -> String {
+		if self.radix != 10 && n.fract() == 0f64 && n.abs() < math::MAX_EXACT_INT as f64 {
+			return self.integer(n as i128);
+		}
+
+		// like JavaScript, very small and very large numbers are in exponent
+		// notation, rather than with dozens of zeros
+		let tiny_or_huge = n != 0f64 && (n.abs() < 1e-6 || n.abs() >= 1e21);
+
+		match (self.notation, self.precision) {
+			(Notation::Auto, None) if tiny_or_huge => format!("{:e}", n),
+			(Notation::Auto, None) => n.to_string(),
+			// rounded, without the trailing zeros fixed notation would show
+			(Notation::Auto, Some(p)) if tiny_or_huge => {
+				let str = format!("{:.*e}", p, n);
+				let (mantissa, exponent) = str.split_once('e').unwrap_or((&str, "0"));
+				format!("{}e{}", trim_zeros(mantissa), exponent)
+			}
+			(Notation::Auto, Some(p)) => match trim_zeros(&format!("{:.*}", p, n)) {
+				"-0" => "0".to_string(),
+				str => str.to_string(),
+			},
+			(Notation::Fixed, p) => format!("{:.*}", p.unwrap_or(6), n),
+			(Notation::Scientific, None) => format!("{:e}", n),
+			(Notation::Scientific, Some(p)) => format!("{:.*e}", p, n),
+		}
+	} // number
+
+	// integers are exact, so only scientific notation rounds them
+	fn integer(&self, n: i128) -> String {
+		let sign = if n < 0 { "-" } else { "" };
+		let abs = n.unsigned_abs();
+
+		match (self.radix, self.notation, self.precision) {
+			(2, ..) => format!("{}0b{:b}", sign, abs),
+			(8, ..) => format!("{}0o{:o}", sign, abs),
+			(16, ..) => format!("{}0x{:x}", sign, abs),
+			(_, Notation::Fixed, p) => format!("{}{}.{}", sign, abs, "0".repeat(p.unwrap_or(6)))
+				.trim_end_matches('.')
+				.to_string(),
+			(_, Notation::Scientific, _) => self.number(n as f64),
+			_ => n.to_string(),
+		}
+	}
+
+	pub fn value(&self, v: &Value) -> String {
+		match v {
+			Value::List(items) => {
+				let items: Vec<String> = items.iter().map(|item| self.value(item)).collect();
+				format!("[{}]", items.join(", "))
+			}
+			Value::Integer(n) => self.integer(*n),
+			Value::Number(n) => self.number(*n),
+			_ => v.to_string(),
+		}
+	}
+} // Format
+
+#[cfg(test)]
+mod tests {
+	use crate::value::*;
+
+	fn format(notation: Notation, precision: Option<usize>, radix: u32) -> Format {
+		Format {
+			notation,
+			precision,
+			radix,
+		}
+	}
+
+	#[test]
+	fn format_numbers() {
+		let auto = Format::default();
+		assert_eq!(auto.number(0.1 + 0.2), "0.30000000000000004");
+		assert_eq!(format(Notation::Auto, Some(4), 10).number(0.1 + 0.2), "0.3");
+		assert_eq!(format(Notation::Auto, Some(2), 10).number(-0.001), "0");
+		assert_eq!(format(Notation::Auto, Some(0), 10).number(1500f64), "1500");
+		assert_eq!(auto.number(6.626_070_15e-34), "6.62607015e-34");
+		assert_eq!(auto.number(-2.5e21), "-2.5e21");
+		assert_eq!(auto.number(0.000_001), "0.000001");
+		assert_eq!(auto.number(1e20), "100000000000000000000");
+		assert_eq!(format(Notation::Auto, Some(4), 10).number(6.626_070_15e-34), "6.6261e-34");
+		assert_eq!(format(Notation::Auto, Some(2), 10).number(1e-7), "1e-7");
+		assert_eq!(format(Notation::Fixed, Some(2), 10).number(1f64 / 3f64), "0.33");
+		assert_eq!(format(Notation::Fixed, None, 10).number(2f64), "2.000000");
+		assert_eq!(format(Notation::Scientific, None, 10).number(1500f64), "1.5e3");
+		assert_eq!(format(Notation::Scientific, Some(2), 10).number(1234f64), "1.23e3");
+		assert_eq!(format(Notation::Auto, None, 16).number(-255f64), "-0xff");
+		assert_eq!(format(Notation::Auto, None, 2).number(5f64), "0b101");
+		assert_eq!(format(Notation::Auto, None, 8).number(2.5), "2.5");
+		assert_eq!(format(Notation::Auto, None, 16).number(2f64.powi(60)), "1152921504606847000");
+
+		let big = Value::integer(1 << 60);
+		assert_eq!(auto.value(&big), "1152921504606846976");
+		assert_eq!(format(Notation::Auto, None, 16).value(&big), "0x1000000000000000");
+		assert_eq!(format(Notation::Fixed, Some(2), 10).value(&big), "1152921504606846976.00");
+		assert_eq!(format(Notation::Scientific, Some(3), 10).value(&big), "1.153e18");
+		assert_eq!(Value::integer(-(1 << 52)), Value::Number(-(2f64.powi(52))));
+		assert_eq!(
+			format(Notation::Auto, None, 16).value(&Value::List(vec![
+				Value::Number(10f64),
+				Value::Duration(90_000)
+			])),
+			"[0xa, 1min 30s]"
+		);
+	}
+}